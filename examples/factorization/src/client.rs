@@ -3,64 +3,62 @@ use std::path::PathBuf;
 use anyhow::Result;
 use log::info;
 use reqwest::Url;
+use tokio::sync::watch;
 use zkvc::client::{ClientApp, ClientConfig};
 use zkvc::response::VerificationResponse;
+use zkvc::worker::{ChallengeSource, WorkerConfig};
 
+use crate::challenge::ChallengeResponse;
 use crate::circuit::FactorizationCircuit;
 
 pub struct FactorizationClient {
     server_url: String,
-    challenge_url: String,
     client_id: String,
 }
 
 impl FactorizationClient {
-    pub fn new(server_url: String, challenge_url: String, client_id: String) -> Self {
+    pub fn new(server_url: String, client_id: String) -> Self {
         Self {
             server_url,
-            challenge_url,
             client_id,
         }
     }
 
-    pub async fn run(&self, p1: Option<u64>, p2: Option<u64>, product: Option<u64>) -> Result<()> {
+    pub async fn run(
+        &self,
+        p1: Option<u64>,
+        p2: Option<u64>,
+        product: Option<u64>,
+        tasks: u64,
+    ) -> Result<()> {
         info!("Starting factorization client {}", self.client_id);
 
-        let (p1, p2, product) = if let (Some(p1), Some(p2), Some(product)) = (p1, p2, product) {
-            info!(
-                "Using provided values: p1={}, p2={}, product={}",
-                p1, p2, product
-            );
-            (p1, p2, product)
-        } else {
-            info!("Requesting challenge from {}", self.challenge_url);
-            let client = reqwest::Client::new();
-            let response = client.get(&self.challenge_url).send().await?;
-            let challenge: crate::challenge::ChallengeResponse = response.json().await?;
-            let product = challenge.product;
-            info!("Received challenge: product={}", product);
-
-            info!("Starting factorization...");
-            let start = std::time::Instant::now();
-            let factors = find_factors(product)
-                .ok_or_else(|| anyhow::anyhow!("Failed to factorize {}", product))?;
-            let elapsed = start.elapsed();
-            info!(
-                "Factorization completed in {:?}. Found factors: {} * {} = {}",
-                elapsed, factors.0, factors.1, product
-            );
-
-            (factors.0, factors.1, product)
-        };
-
         let config = ClientConfig {
-            server_url: Url::parse(&self.server_url)?,
-            proving_key_path: PathBuf::from("fpk.bin"),
             proof_path: Some(PathBuf::from("factor_proof.json")),
-            client_id: self.client_id.clone(),
+            worker: WorkerConfig {
+                max_tasks: (tasks > 0).then_some(tasks),
+                ..WorkerConfig::default()
+            },
+            ..ClientConfig::new(
+                Url::parse(&self.server_url)?,
+                PathBuf::from("fpk.bin"),
+                self.client_id.clone(),
+            )
         };
 
         let client = ClientApp::new(config)?;
+        client.check_server_info().await?;
+
+        let (p1, p2, product) = match (p1, p2, product) {
+            (Some(p1), Some(p2), Some(product)) => {
+                info!(
+                    "Using provided values: p1={}, p2={}, product={}",
+                    p1, p2, product
+                );
+                (p1, p2, product)
+            }
+            _ => return self.work_on_challenges(client).await,
+        };
 
         let circuit = FactorizationCircuit { p1, p2, product };
 
@@ -83,6 +81,36 @@ impl FactorizationClient {
             VerificationResponse::Invalid { reason } => {
                 info!("Invalid proof: {}", reason);
             }
+            VerificationResponse::Malformed { reason, message } => {
+                info!("Malformed request ({:?}): {}", reason, message);
+            }
+            VerificationResponse::Unauthorized { reason } => {
+                info!(
+                    "Request was not accepted as coming from this client: {}",
+                    reason
+                );
+            }
+            VerificationResponse::Rejected { reason, .. } => {
+                info!("Proof was not accepted by the server: {}", reason);
+            }
+            VerificationResponse::Duplicate {} => {
+                info!("Server has already accepted a proof for these public inputs");
+            }
+            VerificationResponse::StaleNonce {} => {
+                info!("Nonce has expired or was already used");
+            }
+            VerificationResponse::Retry { retry_after } => {
+                info!(
+                    "Server asked to retry later (retry after: {:?}s)",
+                    retry_after
+                );
+            }
+            VerificationResponse::RateLimited { retry_after } => {
+                info!("Rate limited, retry after {} seconds", retry_after);
+            }
+            VerificationResponse::Banned { reason, .. } => {
+                info!("Client is banned: {}", reason);
+            }
             VerificationResponse::Error { error } => {
                 info!("Error during proof verification: {}", error);
             }
@@ -90,6 +118,44 @@ impl FactorizationClient {
 
         Ok(())
     }
+
+    async fn work_on_challenges(&self, client: ClientApp) -> Result<()> {
+        let (stop_tx, stop_rx) = watch::channel(false);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("Interrupted, finishing the current task");
+                let _ = stop_tx.send(true);
+            }
+        });
+        let client = client.with_stop_signal(stop_rx);
+
+        let stats = client
+            .run_worker(ChallengeSource::<ChallengeResponse>::new(), |challenge| {
+                let product = challenge.product;
+                info!("Received challenge: product={}", product);
+
+                info!("Starting factorization...");
+                let start = std::time::Instant::now();
+                let (p1, p2) = find_factors(product)
+                    .ok_or_else(|| anyhow::anyhow!("Failed to factorize {}", product))?;
+                info!(
+                    "Factorization completed in {:?}. Found factors: {} * {} = {}",
+                    start.elapsed(),
+                    p1,
+                    p2,
+                    product
+                );
+
+                Ok(Box::new(FactorizationCircuit { p1, p2, product }))
+            })
+            .await?;
+
+        info!(
+            "Client {} factored {} of {} products",
+            self.client_id, stats.valid, stats.tasks
+        );
+        Ok(())
+    }
 }
 
 fn find_factors(n: u64) -> Option<(u64, u64)> {
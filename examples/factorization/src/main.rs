@@ -24,14 +24,10 @@ enum Commands {
         address: String,
         #[arg(long, default_value_t = 32)]
         prime_bits: usize,
-        #[arg(short, long, default_value = "127.0.0.1:65434")]
-        challenge_address: String,
     },
     Client {
         #[arg(short, long, default_value = "http://127.0.0.1:65433")]
         server_url: String,
-        #[arg(long, default_value = "http://127.0.0.1:65434")]
-        challenge_url: String,
         #[arg(long)]
         p1: Option<u64>,
         #[arg(long)]
@@ -40,6 +36,9 @@ enum Commands {
         product: Option<u64>,
         #[arg(short, long, default_value = "client-factorizer-1")]
         client_id: String,
+        /// Number of challenges to solve, 0 to keep working until interrupted
+        #[arg(long, default_value_t = 1)]
+        tasks: u64,
     },
 }
 
@@ -88,21 +87,20 @@ async fn main() -> Result<(), anyhow::Error> {
         Commands::Server {
             address,
             prime_bits,
-            challenge_address,
         } => {
-            let server = server::FactorizationServer::new(address, challenge_address, prime_bits);
+            let server = server::FactorizationServer::new(address, prime_bits);
             server.run().await?;
         }
         Commands::Client {
             server_url,
-            challenge_url,
             p1,
             p2,
             product,
             client_id,
+            tasks,
         } => {
-            let client = client::FactorizationClient::new(server_url, challenge_url, client_id);
-            client.run(p1, p2, product).await?;
+            let client = client::FactorizationClient::new(server_url, client_id);
+            client.run(p1, p2, product, tasks).await?;
         }
     }
 
@@ -2,15 +2,16 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use log::info;
+use tokio::sync::watch;
 use url::Url;
 use zkvc::client::{ClientApp, ClientConfig};
-use zkvc::response::VerificationResponse;
+use zkvc::worker::{ChallengeSource, WorkerConfig};
 
+use crate::challenge::ChallengeResponse;
 use crate::circuit::MatrixMultiplicationCircuit;
 
 pub struct MatrixMultiplicationClient {
     server_url: String,
-    challenge_url: String,
     client_id: String,
     private_matrix: Vec<Vec<u64>>,
     use_hash: bool,
@@ -19,74 +20,75 @@ pub struct MatrixMultiplicationClient {
 impl MatrixMultiplicationClient {
     pub fn new(
         server_url: String,
-        challenge_url: String,
         client_id: String,
         private_matrix: Vec<Vec<u64>>,
         use_hash: bool,
     ) -> Self {
         Self {
             server_url,
-            challenge_url,
             client_id,
             private_matrix,
             use_hash,
         }
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub async fn run(&self, tasks: u64) -> Result<()> {
         info!("Starting matrix multiplication client {}", self.client_id);
 
-        info!("Requesting challenge from {}", self.challenge_url);
-        let client = reqwest::Client::new();
-        let response = client.get(&self.challenge_url).send().await?;
-        let challenge: crate::challenge::ChallengeResponse = response.json().await?;
-
-        let vector = challenge.vector;
-        info!("Received challenge vector: {:?}", vector);
-
-        let m = self.private_matrix[0].len();
-
-        if vector.len() != m {
-            return Err(anyhow::anyhow!(
-                "Vector size {} does not match matrix width {}",
-                vector.len(),
-                m
-            ));
-        }
-
         let config = ClientConfig {
-            server_url: Url::parse(&self.server_url)?,
-            proving_key_path: PathBuf::from("mpk.bin"),
             proof_path: Some(PathBuf::from("matrix_proof.json")),
-            client_id: self.client_id.clone(),
+            worker: WorkerConfig {
+                max_tasks: (tasks > 0).then_some(tasks),
+                ..WorkerConfig::default()
+            },
+            ..ClientConfig::new(
+                Url::parse(&self.server_url)?,
+                PathBuf::from("mpk.bin"),
+                self.client_id.clone(),
+            )
         };
 
-        let client = ClientApp::new(config)?;
+        let (stop_tx, stop_rx) = watch::channel(false);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("Interrupted, finishing the current task");
+                let _ = stop_tx.send(true);
+            }
+        });
 
-        let circuit =
-            MatrixMultiplicationCircuit::new(self.private_matrix.clone(), vector, self.use_hash);
+        let client = ClientApp::new(config)?.with_stop_signal(stop_rx);
+        client.check_server_info().await?;
 
-        info!(
-            "Client {} generating proof for matrix multiplication (hashing: {})",
-            self.client_id, self.use_hash
-        );
+        let m = self.private_matrix[0].len();
+        let stats = client
+            .run_worker(ChallengeSource::<ChallengeResponse>::new(), |challenge| {
+                let vector = challenge.vector;
+                info!("Received challenge vector: {:?}", vector);
 
-        let response = client.generate_and_send_proof(Box::new(circuit)).await?;
-        match response {
-            VerificationResponse::Valid { result } => {
-                info!("Proof is valid! Server verified the zero-knowledge proof.");
-                if let Some(res_inputs) = result {
-                    info!("Public inputs from verified proof: {:?}", res_inputs);
+                if vector.len() != m {
+                    return Err(anyhow::anyhow!(
+                        "Vector size {} does not match matrix width {}",
+                        vector.len(),
+                        m
+                    ));
                 }
-            }
-            VerificationResponse::Invalid { reason } => {
-                info!("Invalid proof: {}", reason);
-            }
-            VerificationResponse::Error { error } => {
-                info!("Error during proof verification: {}", error);
-            }
-        }
 
+                info!(
+                    "Client {} generating proof for matrix multiplication (hashing: {})",
+                    self.client_id, self.use_hash
+                );
+                Ok(Box::new(MatrixMultiplicationCircuit::new(
+                    self.private_matrix.clone(),
+                    vector,
+                    self.use_hash,
+                )))
+            })
+            .await?;
+
+        info!(
+            "Client {} had {} of {} results accepted",
+            self.client_id, stats.valid, stats.tasks
+        );
         Ok(())
     }
 }
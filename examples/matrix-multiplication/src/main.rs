@@ -30,8 +30,6 @@ enum Commands {
     Server {
         #[arg(short, long, default_value = "127.0.0.1:65433")]
         address: String,
-        #[arg(short, long, default_value = "127.0.0.1:65434")]
-        challenge_address: String,
         #[arg(long, default_value_t = 3)]
         matrix_height: usize,
         #[arg(long, default_value_t = 3)]
@@ -40,8 +38,6 @@ enum Commands {
     Client {
         #[arg(short, long, default_value = "http://127.0.0.1:65433")]
         server_url: String,
-        #[arg(long, default_value = "http://127.0.0.1:65434")]
-        challenge_url: String,
         #[arg(short, long, default_value = "client-matrix-1")]
         client_id: String,
         #[arg(long, default_value_t = 3)]
@@ -50,6 +46,9 @@ enum Commands {
         matrix_width: usize,
         #[arg(long, default_value_t = false)]
         use_hash: bool,
+        /// Number of challenges to solve, 0 to keep working until interrupted
+        #[arg(long, default_value_t = 1)]
+        tasks: u64,
     },
 }
 
@@ -111,34 +110,29 @@ async fn main() -> Result<(), anyhow::Error> {
         } => setup_keys(use_hash, matrix_height, matrix_width)?,
         Commands::Server {
             address,
-            challenge_address,
             matrix_height,
             matrix_width,
         } => {
-            let server = server::MatrixMultiplicationServer::new(
-                address,
-                challenge_address,
-                (matrix_height, matrix_width),
-            );
+            let server =
+                server::MatrixMultiplicationServer::new(address, (matrix_height, matrix_width));
             server.run().await?;
         }
         Commands::Client {
             server_url,
-            challenge_url,
             client_id,
             matrix_height,
             matrix_width,
             use_hash,
+            tasks,
         } => {
             let private_matrix = generate_random_matrix(matrix_height, matrix_width);
             let client = client::MatrixMultiplicationClient::new(
                 server_url,
-                challenge_url,
                 client_id,
                 private_matrix,
                 use_hash,
             );
-            client.run().await?;
+            client.run(tasks).await?;
         }
     }
 
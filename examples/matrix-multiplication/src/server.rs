@@ -1,68 +1,38 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use anyhow::Result;
+use ark_bls12_381::Fr;
 use log::info;
 use rand::Rng;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
+use zkvc::challenge::ChallengeProvider;
 use zkvc::server::{ServerApp, ServerConfig};
 use zkvc::utils;
 
+use crate::challenge::ChallengeResponse;
+
 pub struct MatrixMultiplicationServer {
     address: String,
-    challenge_address: String,
     matrix_dimensions: (usize, usize),
 }
 
 impl MatrixMultiplicationServer {
-    pub fn new(
-        address: String,
-        challenge_address: String,
-        matrix_dimensions: (usize, usize),
-    ) -> Self {
+    pub fn new(address: String, matrix_dimensions: (usize, usize)) -> Self {
         Self {
             address,
-            challenge_address,
             matrix_dimensions,
         }
     }
 
     pub async fn run(&self) -> Result<()> {
-        info!(
-            "Starting matrix multiplication server on {} with HTTP API on {}",
-            self.address, self.challenge_address
-        );
+        info!("Starting matrix multiplication server on {}", self.address);
 
         let (_, m) = self.matrix_dimensions;
-        let vector = generate_challenge_vector(m);
-
-        info!("Server generated challenge vector of size {}", m);
-
-        let app_state = AppState {
-            vector: Arc::new(Mutex::new(vector)),
-        };
-
-        let http_state = app_state.clone();
-        let http_server = HttpServer::new(move || {
-            App::new()
-                .app_data(web::Data::new(http_state.clone()))
-                .route("/", web::get().to(get_challenge))
-        })
-        .bind(&self.challenge_address)?
-        .run();
-
-        let http_handle = tokio::spawn(http_server);
-        info!(
-            "HTTP challenge server running at {}",
-            self.challenge_address
-        );
-
-        let config = ServerConfig {
-            listen_address: self.address.clone(),
-            verification_key_path: PathBuf::from("mpk.bin"),
-        };
-
-        let app_state_for_handler = app_state.clone();
+        info!("Server generates challenge vectors of size {}", m);
+
+        let config = ServerConfig::new(self.address.clone(), PathBuf::from("mpk.bin"));
+
         let server = ServerApp::new(config)?
+            .with_challenge_provider(VectorChallenge::new(m))
             .with_valid_proof_handler(move |client_id, public_inputs| {
                 let string_inputs: Vec<String> = public_inputs
                     .iter()
@@ -73,25 +43,10 @@ impl MatrixMultiplicationServer {
                     client_id, string_inputs
                 );
 
-                let (vector_str, result_str) = string_inputs.split_at(m);
-
-                let proved_vector: Vec<u64> =
-                    vector_str.iter().filter_map(|s| s.parse().ok()).collect();
-
-                let mut expected_vector = app_state_for_handler.vector.lock().unwrap();
-
-                if proved_vector == *expected_vector {
-                    info!("Successfully verified: Client provided a valid proof");
-
-                    let proved_result: Vec<u64> =
-                        result_str.iter().filter_map(|s| s.parse().ok()).collect();
-                    info!("Result from proof: {:?}", proved_result);
-
-                    let new_vector = generate_challenge_vector(m);
-                    *expected_vector = new_vector;
-                } else {
-                    info!("Verification MISMATCH: Client used incorrect vector in proof");
-                }
+                let (_, result_str) = string_inputs.split_at(m);
+                let proved_result: Vec<u64> =
+                    result_str.iter().filter_map(|s| s.parse().ok()).collect();
+                info!("Result from proof: {:?}", proved_result);
                 Ok(())
             })
             .with_invalid_proof_handler(|client_id, reason| {
@@ -104,20 +59,46 @@ impl MatrixMultiplicationServer {
             });
 
         server.run().await?;
-        http_handle.abort();
 
         Ok(())
     }
 }
 
-#[derive(Clone)]
-struct AppState {
-    vector: Arc<Mutex<Vec<u64>>>,
+struct VectorChallenge {
+    vector: Mutex<Vec<u64>>,
+    size: usize,
+}
+
+impl VectorChallenge {
+    fn new(size: usize) -> Self {
+        Self {
+            vector: Mutex::new(generate_challenge_vector(size)),
+            size,
+        }
+    }
 }
 
-async fn get_challenge(data: web::Data<AppState>) -> impl Responder {
-    let vector = data.vector.lock().unwrap().clone();
-    HttpResponse::Ok().json(crate::challenge::ChallengeResponse { vector })
+impl ChallengeProvider for VectorChallenge {
+    type Challenge = ChallengeResponse;
+
+    fn issue(&self) -> ChallengeResponse {
+        ChallengeResponse {
+            vector: self.vector.lock().unwrap().clone(),
+        }
+    }
+
+    fn rotate(&self) {
+        *self.vector.lock().unwrap() = generate_challenge_vector(self.size);
+    }
+
+    fn validate(&self, public_inputs: &[Fr]) -> bool {
+        let expected_vector = self.vector.lock().unwrap();
+        public_inputs.len() >= expected_vector.len()
+            && expected_vector
+                .iter()
+                .zip(public_inputs)
+                .all(|(&expected, input)| Fr::from(expected) == *input)
+    }
 }
 
 fn generate_challenge_vector(m: usize) -> Vec<u64> {
@@ -4,10 +4,13 @@ use ark_bls12_381::Fr;
 use clap::{Parser, Subcommand};
 use log::{info, LevelFilter};
 use url::Url;
+use zkvc::circuit::ProofRequest;
 use zkvc::client::{ClientApp, ClientConfig};
+use zkvc::outbox::OutboxConfig;
 use zkvc::response::VerificationResponse;
 use zkvc::server::{ServerApp, ServerConfig};
-use zkvc::{setup, utils};
+use zkvc::tls::TlsConfig;
+use zkvc::{identity, setup, utils};
 
 mod circuit;
 
@@ -24,6 +27,24 @@ enum Commands {
     Server {
         #[arg(short, long, default_value = "127.0.0.1:65432")]
         address: String,
+        /// Public key of a client that must sign its requests, as CLIENT_ID=HEX
+        #[arg(long = "client-key")]
+        client_keys: Vec<String>,
+        /// Serve HTTPS with this PEM certificate chain
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// Require client certificates issued by this PEM CA bundle
+        #[arg(long, requires = "tls_cert")]
+        client_ca: Option<PathBuf>,
+        /// Serve the /admin API (status, pause/resume, clients, submissions, bans, revocations,
+        /// key reload and challenge rotation), authenticated with this bearer token
+        #[arg(long)]
+        admin_token: Option<String>,
+        /// Check vk.bin for changes every this many seconds
+        #[arg(long)]
+        watch_keys: Option<u64>,
     },
     Client {
         #[arg(short, long, default_value = "http://127.0.0.1:65432")]
@@ -34,7 +55,57 @@ enum Commands {
         y: u32,
         #[arg(short, long, default_value = "client-1")]
         client_id: String,
+        #[arg(long)]
+        signing_key: Option<PathBuf>,
+        /// Extra PEM CA bundle to trust, e.g. for a self-signed server certificate
+        #[arg(long)]
+        ca_cert: Option<PathBuf>,
+        /// PEM client certificate for servers that require one
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// Generate proofs on a dedicated pool with this many threads
+        #[arg(long)]
+        prover_threads: Option<usize>,
+        /// Keep proofs in this directory until the server has answered them
+        #[arg(long)]
+        outbox: Option<PathBuf>,
     },
+    /// Generate a proof without contacting the server
+    Prove {
+        #[arg(short, long, default_value = "3")]
+        x: u32,
+        #[arg(short, long, default_value = "5")]
+        y: u32,
+        #[arg(short, long, default_value = "client-1")]
+        client_id: String,
+        #[arg(short, long, default_value = "proof.json")]
+        output: PathBuf,
+    },
+    /// Send a proof written by `prove`
+    Submit {
+        #[arg(short, long, default_value = "http://127.0.0.1:65432")]
+        server_url: String,
+        #[arg(short, long, default_value = "proof.json")]
+        proof: PathBuf,
+        #[arg(long)]
+        signing_key: Option<PathBuf>,
+        #[arg(long)]
+        outbox: Option<PathBuf>,
+    },
+}
+
+struct ClientOptions {
+    signing_key: Option<PathBuf>,
+    prover_threads: Option<usize>,
+    outbox: Option<PathBuf>,
+}
+
+struct ClientTls {
+    ca_cert: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
 }
 
 fn setup() -> Result<(), anyhow::Error> {
@@ -50,7 +121,13 @@ fn setup() -> Result<(), anyhow::Error> {
         &PathBuf::from("vk.bin"),
     )?;
 
-    info!("Setup complete: pk.bin and vk.bin created.");
+    let public_key = identity::generate_signing_key_to_file(&PathBuf::from("client.key"))?;
+    info!(
+        "Client signing key saved to client.key, public key: {}",
+        identity::public_key_to_hex(&public_key)
+    );
+
+    info!("Setup complete: pk.bin, vk.bin and client.key created.");
     Ok(())
 }
 
@@ -59,16 +136,26 @@ async fn run_client(
     x: u32,
     y: u32,
     client_id: String,
+    options: ClientOptions,
+    tls: ClientTls,
 ) -> Result<(), anyhow::Error> {
     info!("Starting client {}", client_id);
     let config = ClientConfig {
-        server_url: Url::parse(&server_url)?,
-        proving_key_path: PathBuf::from("pk.bin"),
         proof_path: Some(PathBuf::from("proof.json")),
-        client_id,
+        signing_key_path: options.signing_key,
+        ca_cert_path: tls.ca_cert,
+        client_cert_path: tls.cert,
+        client_key_path: tls.key,
+        outbox: options.outbox.map(OutboxConfig::new),
+        prover_threads: options.prover_threads,
+        ..ClientConfig::new(Url::parse(&server_url)?, PathBuf::from("pk.bin"), client_id)
     };
 
     let client = ClientApp::new(config)?;
+    client.check_server_info().await?;
+    for response in client.resume_outbox().await? {
+        info!("Pending proof answered: {:?}", response);
+    }
 
     let circuit = circuit::AdderCircuit {
         x: Fr::from(x),
@@ -76,6 +163,60 @@ async fn run_client(
     };
 
     let response = client.generate_and_send_proof(Box::new(circuit)).await?;
+    log_response(response);
+
+    Ok(())
+}
+
+async fn prove(x: u32, y: u32, client_id: String, output: PathBuf) -> Result<(), anyhow::Error> {
+    info!("Generating proof for {} + {} as client {}", x, y, client_id);
+    // Proving never contacts the server, so its address does not matter here.
+    let config = ClientConfig::new(
+        Url::parse("http://127.0.0.1:65432")?,
+        PathBuf::from("pk.bin"),
+        client_id,
+    );
+    let client = ClientApp::new(config)?;
+
+    let circuit = circuit::AdderCircuit {
+        x: Fr::from(x),
+        y: Fr::from(y),
+    };
+    let request = client.prove(Box::new(circuit)).await?;
+    request.save(&output)?;
+    info!("Proof saved to {}", output.display());
+    Ok(())
+}
+
+async fn submit(
+    server_url: String,
+    proof: PathBuf,
+    signing_key: Option<PathBuf>,
+    outbox: Option<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let request = ProofRequest::load(&proof)?;
+    info!(
+        "Submitting proof of client {} from {}",
+        request.client_id(),
+        proof.display()
+    );
+    let config = ClientConfig {
+        signing_key_path: signing_key,
+        outbox: outbox.map(OutboxConfig::new),
+        ..ClientConfig::new(
+            Url::parse(&server_url)?,
+            PathBuf::from("pk.bin"),
+            request.client_id(),
+        )
+    };
+    let client = ClientApp::new(config)?;
+
+    let response = client.submit(request).await?;
+    log_response(response);
+    Ok(())
+}
+
+fn log_response(response: VerificationResponse) {
     match response {
         VerificationResponse::Valid { result } => {
             info!("Proof is valid!");
@@ -86,20 +227,62 @@ async fn run_client(
         VerificationResponse::Invalid { reason } => {
             info!("Invalid proof: {}", reason);
         }
+        VerificationResponse::Malformed { reason, message } => {
+            info!("Malformed request ({:?}): {}", reason, message);
+        }
+        VerificationResponse::Unauthorized { reason } => {
+            info!(
+                "Request was not accepted as coming from this client: {}",
+                reason
+            );
+        }
+        VerificationResponse::Rejected { reason, .. } => {
+            info!("Proof was not accepted by the server: {}", reason);
+        }
+        VerificationResponse::Duplicate {} => {
+            info!("Server has already accepted a proof for these public inputs");
+        }
+        VerificationResponse::StaleNonce {} => {
+            info!("Nonce has expired or was already used");
+        }
+        VerificationResponse::Retry { retry_after } => {
+            info!(
+                "Server asked to retry later (retry after: {:?}s)",
+                retry_after
+            );
+        }
+        VerificationResponse::RateLimited { retry_after } => {
+            info!("Rate limited, retry after {} seconds", retry_after);
+        }
+        VerificationResponse::Banned { reason, .. } => {
+            info!("Client is banned: {}", reason);
+        }
         VerificationResponse::Error { error } => {
             info!("Error: {}", error);
         }
     }
-
-    Ok(())
 }
 
-async fn run_server(address: String) -> Result<(), anyhow::Error> {
+async fn run_server(
+    address: String,
+    client_keys: Vec<String>,
+    tls: Option<TlsConfig>,
+    admin_token: Option<String>,
+    watch_keys: Option<u64>,
+) -> Result<(), anyhow::Error> {
     info!("Starting server on {}", address);
-    let config = ServerConfig {
-        listen_address: address,
-        verification_key_path: PathBuf::from("vk.bin"),
-    };
+    let mut config = ServerConfig::new(address, PathBuf::from("vk.bin"));
+    config.tls = tls;
+    config.admin_token = admin_token;
+    config.key_reload_interval_secs = watch_keys;
+    for entry in client_keys {
+        let (client_id, key) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected CLIENT_ID=HEX, got {}", entry))?;
+        config
+            .client_keys
+            .insert(client_id.to_string(), key.to_string());
+    }
 
     let server = ServerApp::new(config)?
         .with_valid_proof_handler(|client_id, inputs| {
@@ -150,13 +333,60 @@ async fn main() -> Result<(), anyhow::Error> {
 
     match cli.command {
         Commands::Setup => setup()?,
-        Commands::Server { address } => run_server(address).await?,
+        Commands::Server {
+            address,
+            client_keys,
+            tls_cert,
+            tls_key,
+            client_ca,
+            admin_token,
+            watch_keys,
+        } => {
+            let tls = tls_cert
+                .zip(tls_key)
+                .map(|(cert_path, key_path)| TlsConfig {
+                    cert_path,
+                    key_path,
+                    client_ca_path: client_ca,
+                });
+            run_server(address, client_keys, tls, admin_token, watch_keys).await?
+        }
         Commands::Client {
             server_url,
             x,
             y,
             client_id,
-        } => run_client(server_url, x, y, client_id).await?,
+            signing_key,
+            ca_cert,
+            tls_cert,
+            tls_key,
+            prover_threads,
+            outbox,
+        } => {
+            let options = ClientOptions {
+                signing_key,
+                prover_threads,
+                outbox,
+            };
+            let tls = ClientTls {
+                ca_cert,
+                cert: tls_cert,
+                key: tls_key,
+            };
+            run_client(server_url, x, y, client_id, options, tls).await?
+        }
+        Commands::Prove {
+            x,
+            y,
+            client_id,
+            output,
+        } => prove(x, y, client_id, output).await?,
+        Commands::Submit {
+            server_url,
+            proof,
+            signing_key,
+            outbox,
+        } => submit(server_url, proof, signing_key, outbox).await?,
     }
 
     Ok(())
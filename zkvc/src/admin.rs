@@ -0,0 +1,159 @@
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{ledger::unix_millis, ledger::LedgerQuery, response::VerificationResponse};
+
+/// Log target of the audit events written for every admin action.
+pub const AUDIT_TARGET: &str = "zkvc::audit";
+
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+const DEFAULT_SUBMISSIONS_LIMIT: usize = 100;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientSummary {
+    pub client_id: String,
+    pub valid: u64,
+    /// Invalid and malformed submissions.
+    pub invalid: u64,
+    /// Valid proofs refused by a policy, challenge or handler.
+    pub rejected: u64,
+    #[serde(with = "unix_millis")]
+    pub last_seen: SystemTime,
+    /// Seconds left on a ban, if the client is banned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banned_for: Option<u64>,
+    pub revoked: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminStatus {
+    pub paused: bool,
+    pub pending_verifications: usize,
+    pub tracked_clients: usize,
+    pub revoked_clients: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SubmissionsQuery {
+    client_id: Option<String>,
+    circuit_id: Option<String>,
+    /// Unix time in milliseconds.
+    since: Option<u64>,
+    limit: Option<usize>,
+}
+
+impl SubmissionsQuery {
+    pub(crate) fn to_ledger_query(&self) -> LedgerQuery {
+        LedgerQuery {
+            client_id: self.client_id.clone(),
+            circuit_id: self.circuit_id.clone(),
+            since: self
+                .since
+                .map(|millis| UNIX_EPOCH + Duration::from_millis(millis)),
+            until: None,
+            limit: Some(self.limit.unwrap_or(DEFAULT_SUBMISSIONS_LIMIT)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct BanQuery {
+    pub(crate) duration_secs: Option<u64>,
+}
+
+struct ClientCounts {
+    valid: u64,
+    invalid: u64,
+    rejected: u64,
+    last_seen: SystemTime,
+}
+
+/// Per-client submission counts for `/admin/clients`. Callers pass `UNAUTHENTICATED_LABEL`
+/// instead of ids the request could not prove. Once `MAX_TRACKED_CLIENTS` clients are tracked,
+/// the one seen least recently is forgotten to make room.
+pub(crate) struct ClientTracker {
+    clients: Mutex<LruCache<String, ClientCounts>>,
+}
+
+impl ClientTracker {
+    pub(crate) fn new() -> Self {
+        Self::with_capacity(MAX_TRACKED_CLIENTS)
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            clients: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub(crate) fn observe(&self, client_id: &str, response: &VerificationResponse) {
+        let now = SystemTime::now();
+        let mut clients = self.clients.lock().unwrap();
+        let counts = clients.get_or_insert_mut(client_id.to_string(), || ClientCounts {
+            valid: 0,
+            invalid: 0,
+            rejected: 0,
+            last_seen: now,
+        });
+        counts.last_seen = now;
+        match response {
+            VerificationResponse::Valid { .. } => counts.valid += 1,
+            VerificationResponse::Invalid { .. } | VerificationResponse::Malformed { .. } => {
+                counts.invalid += 1
+            }
+            VerificationResponse::Rejected { .. } => counts.rejected += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// Summaries without ban information, most recently seen first.
+    pub(crate) fn summaries(&self) -> Vec<ClientSummary> {
+        self.clients
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(client_id, counts)| ClientSummary {
+                client_id: client_id.clone(),
+                valid: counts.valid,
+                invalid: counts.invalid,
+                rejected: counts.rejected,
+                last_seen: counts.last_seen,
+                banned_for: None,
+                revoked: false,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid() -> VerificationResponse {
+        VerificationResponse::Valid { result: None }
+    }
+
+    #[test]
+    fn least_recently_seen_client_is_evicted() {
+        let tracker = ClientTracker::with_capacity(2);
+        tracker.observe("a", &valid());
+        tracker.observe("b", &valid());
+        tracker.observe("a", &valid());
+        tracker.observe("c", &valid());
+
+        let summaries = tracker.summaries();
+        let ids: Vec<_> = summaries.iter().map(|s| s.client_id.as_str()).collect();
+        assert_eq!(ids, ["c", "a"]);
+        assert_eq!(summaries[1].valid, 2);
+    }
+}
@@ -0,0 +1,93 @@
+use ark_bls12_381::{Bls12_381, Fr, G1Projective};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, PrimeField, UniformRand, Zero};
+use ark_groth16::{prepare_inputs, PreparedVerifyingKey, Proof};
+use rand::thread_rng;
+
+/// Checks all proofs with a single random linear combination of their pairing equations.
+///
+/// A `true` result means every proof is valid; on `false` the caller has to check the proofs one
+/// by one to find out which of them failed.
+pub(crate) fn verify_batch(
+    pvk: &PreparedVerifyingKey<Bls12_381>,
+    items: &[(&Proof<Bls12_381>, &[Fr])],
+) -> Result<bool, anyhow::Error> {
+    if items.is_empty() {
+        return Ok(true);
+    }
+
+    let mut rng = thread_rng();
+    let mut pairs = Vec::with_capacity(items.len() + 2);
+    let mut inputs_sum = G1Projective::zero();
+    let mut c_sum = G1Projective::zero();
+    let mut r_sum = Fr::zero();
+    for (proof, inputs) in items {
+        let r = Fr::rand(&mut rng);
+        let prepared_inputs = prepare_inputs(pvk, inputs)?;
+        inputs_sum += &prepared_inputs.mul(r.into_repr());
+        c_sum += &proof.c.mul(r.into_repr());
+        pairs.push((
+            proof.a.mul(r.into_repr()).into_affine().into(),
+            proof.b.into(),
+        ));
+        r_sum += r;
+    }
+    pairs.push((inputs_sum.into_affine().into(), pvk.gamma_g2_neg_pc.clone()));
+    pairs.push((c_sum.into_affine().into(), pvk.delta_g2_neg_pc.clone()));
+
+    let qap = Bls12_381::miller_loop(pairs.iter());
+    let test = Bls12_381::final_exponentiation(&qap)
+        .ok_or_else(|| anyhow::anyhow!("Unexpected identity in batch pairing check"))?;
+    Ok(test == pvk.alpha_g1_beta_g2.pow(r_sum.into_repr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{keys, prove_with};
+    use ark_groth16::prepare_verifying_key;
+
+    #[test]
+    fn empty_batch_is_valid() {
+        let pvk = prepare_verifying_key(&keys().1);
+        assert!(verify_batch(&pvk, &[]).unwrap());
+    }
+
+    #[test]
+    fn valid_proofs_pass_together() {
+        let pvk = prepare_verifying_key(&keys().1);
+        let proofs: Vec<_> = [(3, 5), (2, 7), (11, 13)]
+            .into_iter()
+            .map(|(a, b)| prove_with(&keys().0, a, b))
+            .collect();
+        let items: Vec<_> = proofs
+            .iter()
+            .map(|(proof, inputs)| (proof, inputs.as_slice()))
+            .collect();
+        assert!(verify_batch(&pvk, &items).unwrap());
+    }
+
+    #[test]
+    fn one_corrupted_proof_fails_the_batch() {
+        let pvk = prepare_verifying_key(&keys().1);
+        let mut proofs: Vec<_> = [(3, 5), (2, 7), (11, 13)]
+            .into_iter()
+            .map(|(a, b)| prove_with(&keys().0, a, b))
+            .collect();
+        proofs[1].0.c = proofs[0].0.c;
+        let items: Vec<_> = proofs
+            .iter()
+            .map(|(proof, inputs)| (proof, inputs.as_slice()))
+            .collect();
+        assert!(!verify_batch(&pvk, &items).unwrap());
+    }
+
+    #[test]
+    fn wrong_public_inputs_fail_the_batch() {
+        let pvk = prepare_verifying_key(&keys().1);
+        let (proof, _) = prove_with(&keys().0, 3, 5);
+        let (other, inputs) = prove_with(&keys().0, 2, 7);
+        let wrong = [Fr::from(16u64)];
+        assert!(!verify_batch(&pvk, &[(&other, &inputs), (&proof, &wrong)]).unwrap());
+    }
+}
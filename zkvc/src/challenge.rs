@@ -0,0 +1,69 @@
+use ark_bls12_381::Fr;
+use serde::{Deserialize, Serialize};
+
+/// Source of the challenges clients are asked to prove against.
+///
+/// The server serves `issue` on the challenge endpoint, checks every valid proof with `validate`
+/// and calls `rotate` once a proof answering the current challenge has been accepted.
+pub trait ChallengeProvider: Send + Sync + 'static {
+    type Challenge: Serialize;
+
+    fn issue(&self) -> Self::Challenge;
+
+    fn rotate(&self);
+
+    fn validate(&self, public_inputs: &[Fr]) -> bool;
+
+    /// Public inputs a proof answering the current challenge carries, for
+    /// `InputPolicy::require_challenge`. Providers that cannot state them keep the default.
+    fn expected_inputs(&self) -> Option<Vec<Fr>> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ChallengeQuery {
+    pub(crate) circuit_id: Option<String>,
+}
+
+pub(crate) trait DynChallengeProvider: Send + Sync {
+    fn issue_json(&self) -> Result<serde_json::Value, anyhow::Error>;
+
+    fn rotate(&self);
+
+    fn validate(&self, public_inputs: &[Fr]) -> bool;
+
+    fn expected_inputs(&self) -> Option<Vec<Fr>>;
+}
+
+impl<P: ChallengeProvider> DynChallengeProvider for P {
+    fn issue_json(&self) -> Result<serde_json::Value, anyhow::Error> {
+        Ok(serde_json::to_value(self.issue())?)
+    }
+
+    fn rotate(&self) {
+        ChallengeProvider::rotate(self)
+    }
+
+    fn validate(&self, public_inputs: &[Fr]) -> bool {
+        ChallengeProvider::validate(self, public_inputs)
+    }
+
+    fn expected_inputs(&self) -> Option<Vec<Fr>> {
+        ChallengeProvider::expected_inputs(self)
+    }
+}
+
+pub(crate) struct RegisteredChallenge {
+    pub(crate) provider: Box<dyn DynChallengeProvider>,
+    pub(crate) consume_lock: tokio::sync::Mutex<()>,
+}
+
+impl RegisteredChallenge {
+    pub(crate) fn new(provider: impl ChallengeProvider) -> Self {
+        Self {
+            provider: Box::new(provider),
+            consume_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+}
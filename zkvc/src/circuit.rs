@@ -1,28 +1,138 @@
+use ark_bls12_381::{Bls12_381, Fr};
 use ark_ff::PrimeField;
+use ark_groth16::Proof;
 use ark_r1cs_std::alloc::AllocVar;
 use ark_r1cs_std::fields::fp::FpVar;
 use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, Namespace, SynthesisError};
+use ark_serialize::CanonicalSerialize;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ed25519_dalek::SigningKey;
 use log::info;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
-#[derive(Serialize, Deserialize)]
+use crate::{identity::sign_request, utils::field_to_string};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct Base64Proof(pub(crate) String);
 
-#[derive(Serialize, Deserialize)]
+pub const DEFAULT_CIRCUIT_ID: &str = "default";
+
+pub(crate) fn default_circuit_id() -> String {
+    DEFAULT_CIRCUIT_ID.to_string()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProofRequest {
     pub(crate) client_id: String,
+    #[serde(default = "default_circuit_id")]
+    pub(crate) circuit_id: String,
     pub(crate) proof: Base64Proof,
     pub(crate) public_inputs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) nonce: Option<String>,
+    /// Base64 ed25519 signature over `identity::signing_message`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) signature: Option<String>,
+}
+
+impl ProofRequest {
+    /// Builds a request from already encoded parts: a base64 uncompressed proof and decimal public
+    /// inputs. Nothing is checked here; `Verifier` answers bad encodings with
+    /// `VerificationResponse::Malformed`.
+    pub fn new(
+        client_id: impl Into<String>,
+        circuit_id: impl Into<String>,
+        proof: impl Into<String>,
+        public_inputs: Vec<String>,
+    ) -> Self {
+        Self {
+            client_id: client_id.into(),
+            circuit_id: circuit_id.into(),
+            proof: Base64Proof(proof.into()),
+            public_inputs,
+            nonce: None,
+            signature: None,
+        }
+    }
+
+    /// Encodes a proof and its public inputs.
+    pub fn from_proof(
+        client_id: impl Into<String>,
+        circuit_id: impl Into<String>,
+        proof: &Proof<Bls12_381>,
+        public_inputs: &[Fr],
+    ) -> Result<Self, anyhow::Error> {
+        let mut proof_bytes = vec![];
+        proof.serialize_uncompressed(&mut proof_bytes)?;
+        let public_inputs = public_inputs
+            .iter()
+            .map(|&input| field_to_string(input))
+            .collect();
+        Ok(Self::new(
+            client_id,
+            circuit_id,
+            STANDARD.encode(&proof_bytes),
+            public_inputs,
+        ))
+    }
+
+    pub fn with_nonce(mut self, nonce: impl Into<String>) -> Self {
+        self.nonce = Some(nonce.into());
+        self
+    }
+
+    /// Signs the request with `key`. Sign after setting the nonce, since the signature covers it.
+    pub fn sign(&mut self, key: &SigningKey) {
+        sign_request(self, key);
+    }
+
+    /// Reads a request written by `save`, e.g. a proof made on another machine.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn circuit_id(&self) -> &str {
+        &self.circuit_id
+    }
+
+    /// The base64 encoded proof.
+    pub fn proof(&self) -> &str {
+        &self.proof.0
+    }
+
+    pub fn public_inputs(&self) -> &[String] {
+        &self.public_inputs
+    }
+
+    pub fn nonce(&self) -> Option<&str> {
+        self.nonce.as_deref()
+    }
+
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
 }
 
 pub struct WrappedConstraintSystem<F: PrimeField> {
     cs: ConstraintSystemRef<F>,
 }
 
-impl<F: PrimeField> Into<Namespace<F>> for WrappedConstraintSystem<F> {
-    fn into(self) -> Namespace<F> {
-        self.cs.into()
+impl<F: PrimeField> From<WrappedConstraintSystem<F>> for Namespace<F> {
+    fn from(wrapped: WrappedConstraintSystem<F>) -> Self {
+        wrapped.cs.into()
     }
 }
 
@@ -67,7 +177,9 @@ impl<F: PrimeField> ZkCircuitContext<F> {
     }
 }
 
-pub trait ConstraintGenerator<F: PrimeField> {
+/// Builds the constraints of a circuit. Generators are `Send` so `ClientApp` can prove them off the
+/// async runtime.
+pub trait ConstraintGenerator<F: PrimeField>: Send {
     fn generate_constraints(&self, context: &mut ZkCircuitContext<F>)
         -> Result<(), SynthesisError>;
 }
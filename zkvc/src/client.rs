@@ -1,21 +1,27 @@
 use crate::{
-    circuit::{Base64Proof, ConstraintGenerator, ProofRequest, ZkCircuit},
-    response::VerificationResponse,
-    utils::{field_to_string, VERIFY_PATH},
+    challenge::ChallengeQuery,
+    circuit::{default_circuit_id, ConstraintGenerator, ProofRequest, ZkCircuit},
+    identity::{load_signing_key, sign_request},
+    outbox::{Outbox, OutboxConfig},
+    response::{NonceResponse, ServerInfo, VerificationResponse, CURVE, PROTOCOL_VERSION},
+    utils::{key_fingerprint, CHALLENGE_PATH, INFO_PATH, NONCE_PATH, VERIFY_PATH},
+    worker::{self, Backoff, NextTask, TaskSource, WorkerConfig, WorkerStats},
 };
 use ark_bls12_381::{Bls12_381, Fr};
-use ark_groth16::{create_random_proof, ProvingKey};
-use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
-use base64::{engine::general_purpose::STANDARD, Engine as _};
-use log::{debug, info};
+use ark_groth16::{create_random_proof, Proof, ProvingKey};
+use ark_serialize::CanonicalDeserialize;
+use ed25519_dalek::SigningKey;
+use log::{debug, info, warn};
 use rand::thread_rng;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use reqwest::{Certificate, Client, Identity};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Instant,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
 };
+use tokio::sync::watch;
 use url::Url;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -24,87 +30,198 @@ pub struct ClientConfig {
     pub proving_key_path: PathBuf,
     pub proof_path: Option<PathBuf>,
     pub client_id: String,
+    #[serde(default = "default_circuit_id")]
+    pub circuit_id: String,
+    /// ed25519 secret key used to sign every request, see `identity::generate_signing_key_to_file`.
+    #[serde(default)]
+    pub signing_key_path: Option<PathBuf>,
+    /// PEM bundle of extra CAs to trust, e.g. for a server with a self-signed certificate.
+    #[serde(default)]
+    pub ca_cert_path: Option<PathBuf>,
+    /// PEM certificate chain presented to servers that require client certificates.
+    #[serde(default)]
+    pub client_cert_path: Option<PathBuf>,
+    /// PEM private key for `client_cert_path`.
+    #[serde(default)]
+    pub client_key_path: Option<PathBuf>,
+    /// Keep proofs on disk until the server has answered them, retrying with backoff.
+    #[serde(default)]
+    pub outbox: Option<OutboxConfig>,
+    /// Threads of the dedicated pool proofs are generated on. Unset, proving shares rayon's global
+    /// pool with the rest of the process.
+    #[serde(default)]
+    pub prover_threads: Option<usize>,
+    /// Backoff and task limit for `ClientApp::run_worker`.
+    #[serde(default)]
+    pub worker: WorkerConfig,
+}
+
+impl ClientConfig {
+    pub fn new(
+        server_url: Url,
+        proving_key_path: impl Into<PathBuf>,
+        client_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            server_url,
+            proving_key_path: proving_key_path.into(),
+            proof_path: None,
+            client_id: client_id.into(),
+            circuit_id: default_circuit_id(),
+            signing_key_path: None,
+            ca_cert_path: None,
+            client_cert_path: None,
+            client_key_path: None,
+            outbox: None,
+            prover_threads: None,
+            worker: WorkerConfig::default(),
+        }
+    }
 }
 
 pub struct ClientApp {
     config: ClientConfig,
-    proving_key: ProvingKey<Bls12_381>,
+    proving_key: Arc<LazyProvingKey>,
+    prover_pool: Option<Arc<ThreadPool>>,
+    signing_key: Option<SigningKey>,
+    http: Client,
+    outbox: Option<Outbox>,
+    stop_signal: Option<watch::Receiver<bool>>,
 }
 
 impl ClientApp {
     pub fn new(config: ClientConfig) -> Result<Self, anyhow::Error> {
         debug!("Creating new ClientApp instance");
-        let start = Instant::now();
-        let pk_bytes = std::fs::read(&config.proving_key_path)?;
-        let pk = ProvingKey::deserialize_unchecked(&*pk_bytes)?;
-        info!("Proving key loaded successfully in {:?}", start.elapsed());
+        let signing_key = config
+            .signing_key_path
+            .as_deref()
+            .map(load_signing_key)
+            .transpose()?;
+
+        let http = build_http_client(&config)?;
+
+        let outbox = config.outbox.as_ref().map(Outbox::open).transpose()?;
+
+        let proving_key = Arc::new(LazyProvingKey {
+            path: config.proving_key_path.clone(),
+            key: OnceLock::new(),
+        });
+
+        let prover_pool = config
+            .prover_threads
+            .map(|threads| {
+                debug!("Starting prover pool with {} threads", threads);
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|index| format!("zkvc-prover-{}", index))
+                    .build()
+            })
+            .transpose()?
+            .map(Arc::new);
 
         Ok(Self {
             config,
-            proving_key: pk,
+            proving_key,
+            prover_pool,
+            signing_key,
+            http,
+            outbox,
+            stop_signal: None,
         })
     }
 
-    fn generate_proof_request(
+    /// Makes `run_worker` return once `true` is sent on the channel. A proof that is already being
+    /// generated or submitted is finished first.
+    pub fn with_stop_signal(mut self, stop_signal: watch::Receiver<bool>) -> Self {
+        self.stop_signal = Some(stop_signal);
+        self
+    }
+
+    /// Generates a proof without contacting the server. The request can be stored with
+    /// `ProofRequest::save` and sent later, possibly from another host, with `submit`.
+    pub async fn prove(
         &self,
         generator: Box<dyn ConstraintGenerator<Fr>>,
     ) -> Result<ProofRequest, anyhow::Error> {
         debug!("Generating proof request");
         let start = Instant::now();
 
-        let public_inputs: Arc<Mutex<Vec<Fr>>> = Arc::new(Mutex::new(Vec::new()));
-        let circuit = ZkCircuit {
-            generator,
-            public_inputs: Arc::clone(&public_inputs),
-        };
-
-        let mut rng = thread_rng();
-        let proof = create_random_proof::<Bls12_381, _, _>(circuit, &self.proving_key, &mut rng)?;
+        let proving_key = Arc::clone(&self.proving_key);
+        let prover_pool = self.prover_pool.clone();
+        let (proof, public_inputs) = tokio::task::spawn_blocking(move || {
+            let proving_key = proving_key.get()?;
+            let prove = || prove(generator, proving_key);
+            match prover_pool {
+                Some(pool) => pool.install(prove),
+                None => prove(),
+            }
+        })
+        .await??;
         debug!("Proof generated successfully in {:?}", start.elapsed());
 
         let start_serialize = Instant::now();
-        let mut proof_bytes = vec![];
-        proof.serialize_uncompressed(&mut proof_bytes)?;
-        let base64_proof = Base64Proof(STANDARD.encode(&proof_bytes));
+        let request = ProofRequest::from_proof(
+            self.config.client_id.clone(),
+            self.config.circuit_id.clone(),
+            &proof,
+            &public_inputs,
+        )?;
         debug!(
             "Proof serialized and encoded in {:?}",
             start_serialize.elapsed()
         );
-
-        let public_inputs = public_inputs.lock().unwrap();
-        let public_inputs: Vec<String> = public_inputs
-            .iter()
-            .map(|&input| field_to_string(input))
-            .collect();
-
-        Ok(ProofRequest {
-            client_id: self.config.client_id.clone(),
-            proof: base64_proof,
-            public_inputs,
-        })
+        Ok(request)
     }
 
     fn save_proof(&self, request: &ProofRequest) -> Result<(), anyhow::Error> {
         if let Some(path) = &self.config.proof_path {
             debug!("Saving proof request to {}", path.display());
-            let json = serde_json::to_string(request)?;
-            std::fs::write(path, json)?;
+            request.save(path)?;
             info!("Proof request saved successfully");
         }
         Ok(())
     }
 
-    async fn send_proof(
+    /// Sends a proof made by `prove`, adding a nonce and this client's signature. With an outbox
+    /// configured the proof is kept until the server has answered it.
+    pub async fn submit(
         &self,
-        request: ProofRequest,
+        mut request: ProofRequest,
+    ) -> Result<VerificationResponse, anyhow::Error> {
+        self.prepare_request(&mut request).await;
+        match &self.outbox {
+            Some(outbox) => {
+                let path = outbox.store(&request)?;
+                self.deliver(outbox, &path, request).await
+            }
+            None => self.post_proof(&request).await,
+        }
+    }
+
+    /// Adds a nonce, unless the request already has one, and this client's signature.
+    async fn prepare_request(&self, request: &mut ProofRequest) {
+        if request.nonce.is_none() {
+            match self.fetch_nonce().await {
+                Ok(nonce) => request.nonce = Some(nonce),
+                Err(e) => warn!("Failed to fetch nonce, sending proof without one: {}", e),
+            }
+        }
+        if let Some(key) = &self.signing_key {
+            sign_request(request, key);
+        }
+    }
+
+    async fn post_proof(
+        &self,
+        request: &ProofRequest,
     ) -> Result<VerificationResponse, anyhow::Error> {
         debug!("Sending proof to server at {}", self.config.server_url);
         let start = Instant::now();
 
-        let client = Client::new();
-        let resp = client
-            .post(self.config.server_url.join(VERIFY_PATH)?)
-            .json(&request)
+        let resp = self
+            .http
+            .post(self.endpoint(VERIFY_PATH)?)
+            .json(request)
             .send()
             .await?;
 
@@ -113,14 +230,358 @@ impl ClientApp {
         Ok(response)
     }
 
+    /// Submits the outbox entry at `path` until the server gives a final answer, then removes it.
+    ///
+    /// Every attempt sends the stored nonce and signature, so a proof the server accepted while its
+    /// answer got lost comes back as `Duplicate` and counts as delivered. Entries still without an
+    /// answer after `OutboxConfig::max_attempts` stay for `resume_outbox`.
+    async fn deliver(
+        &self,
+        outbox: &Outbox,
+        path: &Path,
+        mut request: ProofRequest,
+    ) -> Result<VerificationResponse, anyhow::Error> {
+        let max_attempts = outbox.max_attempts();
+        let mut stop = self.stop_signal.clone();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let outcome = self.post_proof(&request).await;
+            let wait = match &outcome {
+                Ok(VerificationResponse::Retry { retry_after }) => {
+                    retry_after.map(Duration::from_secs)
+                }
+                Ok(VerificationResponse::RateLimited { retry_after }) => {
+                    Some(Duration::from_secs(*retry_after))
+                }
+                Ok(VerificationResponse::Error { error }) => {
+                    warn!("Server failed to handle {}: {}", path.display(), error);
+                    None
+                }
+                Ok(VerificationResponse::StaleNonce {}) if request.nonce.is_some() => {
+                    // The server forgets accepted submissions together with their nonces, so a
+                    // fresh nonce cannot get this proof counted twice.
+                    info!(
+                        "Nonce of {} has expired, fetching a new one",
+                        path.display()
+                    );
+                    request.nonce = None;
+                    self.prepare_request(&mut request).await;
+                    outbox.update(path, &request)?;
+                    None
+                }
+                Ok(response) => {
+                    if attempt > 1 && matches!(response, VerificationResponse::Duplicate {}) {
+                        info!("Server had already accepted {}", path.display());
+                    }
+                    outbox.remove(path)?;
+                    return outcome;
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to submit {} (attempt {}/{}): {}",
+                        path.display(),
+                        attempt,
+                        max_attempts,
+                        e
+                    );
+                    None
+                }
+            };
+
+            if attempt >= max_attempts {
+                warn!(
+                    "{} stays in the outbox after {} attempts",
+                    path.display(),
+                    attempt
+                );
+                return outcome;
+            }
+            let wait = wait.unwrap_or_else(|| outbox.retry_delay(attempt));
+            debug!("Resubmitting {} in {:?}", path.display(), wait);
+            if !worker::pause(wait, &mut stop).await {
+                info!("Stopped, {} stays in the outbox", path.display());
+                return outcome;
+            }
+        }
+    }
+
+    /// Resubmits the proofs an earlier run left in the outbox, oldest first, and returns the
+    /// answers the server gave.
+    ///
+    /// Entries that fail for a reason of their own, such as an unreadable file or a response that
+    /// cannot be decoded, are logged, left in the outbox and skipped, so they do not hold up the
+    /// ones behind them. Once the server cannot be reached at all, this stops with that error and
+    /// leaves the remaining entries for the next call.
+    pub async fn resume_outbox(&self) -> Result<Vec<VerificationResponse>, anyhow::Error> {
+        let Some(outbox) = &self.outbox else {
+            return Ok(Vec::new());
+        };
+        let pending = outbox.pending()?;
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        info!(
+            "Resuming {} pending proofs from {}",
+            pending.len(),
+            outbox.dir().display()
+        );
+
+        let mut responses = Vec::with_capacity(pending.len());
+        for path in pending {
+            let request = match ProofRequest::load(&path) {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!("Skipping unreadable outbox entry {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            match self.deliver(outbox, &path, request).await {
+                Ok(response) => responses.push(response),
+                Err(e) if is_unreachable(&e) => return Err(e),
+                Err(e) => warn!("Skipping outbox entry {}: {}", path.display(), e),
+            }
+        }
+        Ok(responses)
+    }
+
+    async fn fetch_nonce(&self) -> Result<String, anyhow::Error> {
+        debug!("Requesting nonce from {}", self.config.server_url);
+        let resp = self
+            .http
+            .get(self.endpoint(NONCE_PATH)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.json::<NonceResponse>().await?.nonce)
+    }
+
+    pub async fn fetch_challenge<T: DeserializeOwned>(&self) -> Result<T, anyhow::Error> {
+        debug!(
+            "Requesting challenge for circuit {} from {}",
+            self.config.circuit_id, self.config.server_url
+        );
+        let resp = self
+            .http
+            .get(self.endpoint(CHALLENGE_PATH)?)
+            .query(&ChallengeQuery {
+                circuit_id: Some(self.config.circuit_id.clone()),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.json::<T>().await?)
+    }
+
+    pub async fn fetch_server_info(&self) -> Result<ServerInfo, anyhow::Error> {
+        debug!("Requesting server info from {}", self.config.server_url);
+        let resp = self
+            .http
+            .get(self.endpoint(INFO_PATH)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.json::<ServerInfo>().await?)
+    }
+
+    /// Checks that the server speaks the same protocol and verifies this client's circuit with the
+    /// key matching the local proving key, so a mismatch is caught before any proving work.
+    pub async fn check_server_info(&self) -> Result<ServerInfo, anyhow::Error> {
+        let info = self.fetch_server_info().await?;
+        if info.protocol_version != PROTOCOL_VERSION {
+            return Err(anyhow::anyhow!(
+                "Server speaks protocol version {}, expected {}",
+                info.protocol_version,
+                PROTOCOL_VERSION
+            ));
+        }
+        if info.curve != CURVE {
+            return Err(anyhow::anyhow!(
+                "Server uses curve {}, expected {}",
+                info.curve,
+                CURVE
+            ));
+        }
+
+        let circuit = info.circuits.get(&self.config.circuit_id).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Server does not verify circuit {}, known circuits: {:?}",
+                self.config.circuit_id,
+                info.circuits.keys().collect::<Vec<_>>()
+            )
+        })?;
+        let proving_key = Arc::clone(&self.proving_key);
+        let fingerprint = tokio::task::spawn_blocking(move || {
+            proving_key.get().map(|key| key_fingerprint(&key.vk))
+        })
+        .await??;
+        if circuit.key_fingerprint != fingerprint {
+            return Err(anyhow::anyhow!(
+                "Verification key for circuit {} does not match the proving key: server has {}, client has {}",
+                self.config.circuit_id,
+                circuit.key_fingerprint,
+                fingerprint
+            ));
+        }
+
+        info!(
+            "Server verifies circuit {} with a matching key ({} public inputs)",
+            self.config.circuit_id, circuit.public_inputs
+        );
+        Ok(info)
+    }
+
     pub async fn generate_and_send_proof(
         &self,
         generator: Box<dyn ConstraintGenerator<Fr>>,
     ) -> Result<VerificationResponse, anyhow::Error> {
         debug!("Generating and sending proof");
-        let proof_request = self.generate_proof_request(generator)?;
+        let proof_request = self.prove(generator).await?;
         self.save_proof(&proof_request)?;
-        self.send_proof(proof_request).await
+        self.submit(proof_request).await
+    }
+
+    /// Runs as a volunteer: takes tasks from `source`, proves the circuit `factory` builds for each
+    /// and submits it, until the source is exhausted, `WorkerConfig::max_tasks` is reached or the
+    /// stop signal fires.
+    ///
+    /// The worker backs off when there is no work, the server cannot be reached or it asks the
+    /// client to wait, and gives up when the server refuses the client for good.
+    pub async fn run_worker<S, F>(
+        &self,
+        mut source: S,
+        mut factory: F,
+    ) -> Result<WorkerStats, anyhow::Error>
+    where
+        S: TaskSource,
+        F: FnMut(S::Task) -> Result<Box<dyn ConstraintGenerator<Fr>>, anyhow::Error>,
+    {
+        let config = &self.config.worker;
+        info!(
+            "Worker {} started for circuit {}",
+            self.config.client_id, self.config.circuit_id
+        );
+        let start = Instant::now();
+        if let Err(e) = self.resume_outbox().await {
+            warn!("Failed to resume pending proofs: {}", e);
+        }
+        let mut stop = self.stop_signal.clone();
+        let mut backoff = Backoff::new(config);
+        let mut stats = WorkerStats::default();
+
+        while config.max_tasks.is_none_or(|max| stats.tasks < max) && !worker::is_stopped(&stop) {
+            let task = match source.next_task(self).await {
+                Ok(NextTask::Task(task)) => task,
+                Ok(NextTask::Idle) => {
+                    let wait = backoff.next();
+                    debug!("No task available, waiting {:?}", wait);
+                    if !worker::pause(wait, &mut stop).await {
+                        break;
+                    }
+                    continue;
+                }
+                Ok(NextTask::Exhausted) => {
+                    info!("Task source is exhausted");
+                    break;
+                }
+                Err(e) => {
+                    let wait = backoff.next();
+                    warn!("Failed to fetch a task, retrying in {:?}: {}", wait, e);
+                    if !worker::pause(wait, &mut stop).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            stats.tasks += 1;
+
+            let generator = match factory(task) {
+                Ok(generator) => generator,
+                Err(e) => {
+                    warn!("Failed to build a circuit for task {}: {}", stats.tasks, e);
+                    stats.failed += 1;
+                    continue;
+                }
+            };
+
+            let response = match self.generate_and_send_proof(generator).await {
+                Ok(response) => response,
+                Err(e) => {
+                    let wait = backoff.next();
+                    warn!(
+                        "Failed to prove or submit task {}, retrying in {:?}: {}",
+                        stats.tasks, wait, e
+                    );
+                    stats.failed += 1;
+                    if !worker::pause(wait, &mut stop).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            stats.record(&response);
+
+            let wait = match &response {
+                VerificationResponse::Valid { .. } => {
+                    info!("Task {} accepted", stats.tasks);
+                    backoff.reset();
+                    None
+                }
+                VerificationResponse::Duplicate {} => {
+                    // Someone already answered this task, so the source may hand it out again.
+                    info!("Task {} was already answered", stats.tasks);
+                    Some(backoff.next())
+                }
+                VerificationResponse::Retry { retry_after } => {
+                    Some(retry_after.map_or_else(|| backoff.next(), Duration::from_secs))
+                }
+                VerificationResponse::RateLimited { retry_after }
+                | VerificationResponse::Banned {
+                    retry_after: Some(retry_after),
+                    ..
+                } => Some(Duration::from_secs(*retry_after)),
+                VerificationResponse::Unauthorized { reason }
+                | VerificationResponse::Banned { reason, .. } => {
+                    return Err(anyhow::anyhow!(
+                        "Server refused client {}: {}",
+                        self.config.client_id,
+                        reason
+                    ));
+                }
+                other => {
+                    warn!("Task {} was not accepted: {:?}", stats.tasks, other);
+                    backoff.reset();
+                    None
+                }
+            };
+            if let Some(wait) = wait {
+                debug!("Waiting {:?} before the next task", wait);
+                if !worker::pause(wait, &mut stop).await {
+                    break;
+                }
+            }
+        }
+
+        info!(
+            "Worker {} stopped after {} tasks in {:?}: {:?}",
+            self.config.client_id,
+            stats.tasks,
+            start.elapsed(),
+            stats
+        );
+        Ok(stats)
+    }
+
+    /// Resolves `path` below `server_url`, keeping any prefix the server is mounted under.
+    fn endpoint(&self, path: &str) -> Result<Url, url::ParseError> {
+        let mut base = self.config.server_url.clone();
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        base.join(path.trim_start_matches('/'))
     }
 
     pub fn get_proof_path(&self) -> Option<&PathBuf> {
@@ -134,4 +595,289 @@ impl ClientApp {
     pub fn get_client_id(&self) -> &str {
         &self.config.client_id
     }
+
+    pub fn get_circuit_id(&self) -> &str {
+        &self.config.circuit_id
+    }
+}
+
+/// Proving key read from `ClientConfig::proving_key_path` on first use, so a host that only submits
+/// saved proofs does not need it.
+struct LazyProvingKey {
+    path: PathBuf,
+    key: OnceLock<ProvingKey<Bls12_381>>,
+}
+
+impl LazyProvingKey {
+    fn get(&self) -> Result<&ProvingKey<Bls12_381>, anyhow::Error> {
+        if let Some(key) = self.key.get() {
+            return Ok(key);
+        }
+        let start = Instant::now();
+        let pk_bytes = std::fs::read(&self.path)?;
+        let pk = ProvingKey::deserialize_unchecked(&*pk_bytes)?;
+        info!("Proving key loaded successfully in {:?}", start.elapsed());
+        Ok(self.key.get_or_init(|| pk))
+    }
+}
+
+fn prove(
+    generator: Box<dyn ConstraintGenerator<Fr>>,
+    proving_key: &ProvingKey<Bls12_381>,
+) -> Result<(Proof<Bls12_381>, Vec<Fr>), anyhow::Error> {
+    let public_inputs: Arc<Mutex<Vec<Fr>>> = Arc::new(Mutex::new(Vec::new()));
+    let circuit = ZkCircuit {
+        generator,
+        public_inputs: Arc::clone(&public_inputs),
+    };
+
+    let mut rng = thread_rng();
+    let proof = create_random_proof::<Bls12_381, _, _>(circuit, proving_key, &mut rng)?;
+    let public_inputs = std::mem::take(&mut *public_inputs.lock().unwrap());
+    Ok((proof, public_inputs))
+}
+
+/// Whether `error` means the server could not be reached, rather than that one request failed.
+fn is_unreachable(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect() || e.is_timeout())
+}
+
+fn build_http_client(config: &ClientConfig) -> Result<Client, anyhow::Error> {
+    let mut builder = Client::builder().use_rustls_tls();
+    if let Some(path) = &config.ca_cert_path {
+        for cert in Certificate::from_pem_bundle(&std::fs::read(path)?)? {
+            builder = builder.add_root_certificate(cert);
+        }
+        debug!("Trusting CA certificates from {}", path.display());
+    }
+    match (&config.client_cert_path, &config.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let mut pem = std::fs::read(cert_path)?;
+            pem.push(b'\n');
+            pem.extend(std::fs::read(key_path)?);
+            builder = builder.identity(Identity::from_pem(&pem)?);
+            debug!("Using client certificate {}", cert_path.display());
+        }
+        (None, None) => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "client_cert_path and client_key_path must be set together"
+            ))
+        }
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::DEFAULT_CIRCUIT_ID,
+        server::{ServerApp, ServerHandle},
+        setup::generate_keys,
+        test_utils::{client_config, server_config, write_key, ProductCircuit},
+    };
+
+    fn start_server(dir: &Path) -> ServerHandle {
+        ServerApp::new(server_config(dir)).unwrap().start().unwrap()
+    }
+
+    async fn stop(handle: ServerHandle) {
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn saved_proof_is_submitted_from_a_client_without_proving_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+
+        let prover_dir = tempfile::tempdir().unwrap();
+        let prover = ClientApp::new(client_config(
+            prover_dir.path(),
+            handle.local_addr(),
+            "client",
+        ))
+        .unwrap();
+        let request = prover
+            .prove(Box::new(ProductCircuit { a: 3, b: 5 }))
+            .await
+            .unwrap();
+        let path = dir.path().join("proof.json");
+        request.save(&path).unwrap();
+
+        let url = format!("http://{}", handle.local_addr()).parse().unwrap();
+        let missing_key = dir.path().join("no-such-pk.bin");
+        let submitter = ClientApp::new(ClientConfig::new(url, &missing_key, "client")).unwrap();
+        let response = submitter
+            .submit(ProofRequest::load(&path).unwrap())
+            .await
+            .unwrap();
+        assert!(
+            matches!(response, VerificationResponse::Valid { .. }),
+            "{:?}",
+            response
+        );
+        assert!(!missing_key.exists());
+
+        stop(handle).await;
+    }
+
+    #[tokio::test]
+    async fn server_info_check_accepts_the_matching_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+
+        let client =
+            ClientApp::new(client_config(dir.path(), handle.local_addr(), "client")).unwrap();
+        let info = client.check_server_info().await.unwrap();
+        assert!(info.circuits.contains_key(DEFAULT_CIRCUIT_ID));
+
+        stop(handle).await;
+    }
+
+    #[tokio::test]
+    async fn server_info_check_refuses_a_different_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+
+        let client_dir = tempfile::tempdir().unwrap();
+        let config = client_config(client_dir.path(), handle.local_addr(), "client");
+        let (other_key, _) = generate_keys(Box::new(ProductCircuit { a: 1, b: 1 })).unwrap();
+        write_key(&config.proving_key_path, &other_key);
+        let client = ClientApp::new(config).unwrap();
+
+        let error = client.check_server_info().await.unwrap_err().to_string();
+        assert!(
+            error.contains("does not match the proving key"),
+            "{}",
+            error
+        );
+
+        stop(handle).await;
+    }
+
+    #[tokio::test]
+    async fn server_info_check_refuses_an_unknown_circuit() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+
+        let mut config = client_config(dir.path(), handle.local_addr(), "client");
+        config.circuit_id = "other".to_string();
+        let client = ClientApp::new(config).unwrap();
+
+        let error = client.check_server_info().await.unwrap_err().to_string();
+        assert!(error.contains("does not verify circuit other"), "{}", error);
+
+        stop(handle).await;
+    }
+
+    #[tokio::test]
+    async fn client_submits_to_a_verifier_mounted_under_a_prefix() {
+        use actix_web::{middleware::DefaultHeaders, web, App, HttpResponse, HttpServer};
+
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(server_config(dir.path()))
+            .unwrap()
+            .into_service();
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap(DefaultHeaders::new().add(("X-Served-By", "host")))
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .service(service.scope("/zk/v1"))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let server_handle = server.handle();
+        tokio::spawn(server);
+
+        let http = reqwest::Client::new();
+        let health = http
+            .get(format!("http://{}/health", address))
+            .send()
+            .await
+            .unwrap();
+        assert!(health.status().is_success());
+        let info = http
+            .get(format!("http://{}/zk/v1{}", address, INFO_PATH))
+            .send()
+            .await
+            .unwrap();
+        assert!(info.status().is_success());
+        assert_eq!(info.headers()["x-served-by"], "host");
+
+        for (index, url) in ["/zk/v1", "/zk/v1/"].into_iter().enumerate() {
+            let mut config = client_config(dir.path(), address, "client");
+            config.server_url = format!("http://{}{}", address, url).parse().unwrap();
+            let client = ClientApp::new(config).unwrap();
+            client.check_server_info().await.unwrap();
+
+            let a = 3 + index as u64;
+            let mut request = client
+                .prove(Box::new(ProductCircuit { a, b: 5 }))
+                .await
+                .unwrap();
+            // Fetched here because `submit` only warns when it cannot get a nonce.
+            request.nonce = Some(client.fetch_nonce().await.unwrap());
+            let response = client.submit(request).await.unwrap();
+            assert!(
+                matches!(response, VerificationResponse::Valid { .. }),
+                "{}: {:?}",
+                url,
+                response
+            );
+        }
+
+        server_handle.stop(true).await;
+    }
+
+    /// Records the thread constraints were generated on.
+    struct ThreadRecorder {
+        circuit: ProductCircuit,
+        thread: Arc<Mutex<Option<std::thread::Thread>>>,
+    }
+
+    impl ConstraintGenerator<Fr> for ThreadRecorder {
+        fn generate_constraints(
+            &self,
+            context: &mut crate::circuit::ZkCircuitContext<Fr>,
+        ) -> Result<(), ark_relations::r1cs::SynthesisError> {
+            *self.thread.lock().unwrap() = Some(std::thread::current());
+            self.circuit.generate_constraints(context)
+        }
+    }
+
+    async fn proving_thread(client: &ClientApp) -> std::thread::Thread {
+        let thread = Arc::new(Mutex::new(None));
+        client
+            .prove(Box::new(ThreadRecorder {
+                circuit: ProductCircuit { a: 3, b: 5 },
+                thread: Arc::clone(&thread),
+            }))
+            .await
+            .unwrap();
+        let thread = thread.lock().unwrap().take();
+        thread.unwrap()
+    }
+
+    #[tokio::test]
+    async fn proofs_are_generated_off_the_runtime_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = "127.0.0.1:1".parse().unwrap();
+
+        let client = ClientApp::new(client_config(dir.path(), address, "client")).unwrap();
+        let thread = proving_thread(&client).await;
+        assert_ne!(thread.id(), std::thread::current().id());
+
+        let mut config = client_config(dir.path(), address, "client");
+        config.prover_threads = Some(1);
+        let client = ClientApp::new(config).unwrap();
+        let thread = proving_thread(&client).await;
+        assert_eq!(thread.name(), Some("zkvc-prover-0"));
+    }
 }
@@ -0,0 +1,255 @@
+use ark_bls12_381::Fr;
+use std::{future::Future, pin::Pin};
+
+pub type HandlerFuture<'a, T = ()> =
+    Pin<Box<dyn Future<Output = Result<T, anyhow::Error>> + Send + 'a>>;
+
+/// Application-level decision about a proof that passed the cryptographic check. Over HTTP a
+/// `Reject` is answered with 422 and a `Retry` with 503 and `Retry-After` set to
+/// `verify_retry_after_secs`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Accept { result: Option<Vec<String>> },
+    Reject { reason: String },
+    Retry,
+}
+
+/// Callbacks invoked by `ServerApp` once a proof has been checked.
+///
+/// Every method defaults to a no-op, so implementors only override what they need. The returned
+/// futures are awaited before the verification response is sent, and the `Verdict` returned by
+/// `on_valid` decides what the client is told. An error from `on_invalid` is only logged: the
+/// client still gets the rejection its proof earned.
+pub trait ProofHandler: Send + Sync + 'static {
+    fn on_valid<'a>(
+        &'a self,
+        _client_id: &'a str,
+        _public_inputs: &'a [Fr],
+    ) -> HandlerFuture<'a, Verdict> {
+        Box::pin(async { Ok(Verdict::Accept { result: None }) })
+    }
+
+    fn on_invalid<'a>(&'a self, _client_id: &'a str, _reason: &'a str) -> HandlerFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn on_error<'a>(&'a self, _client_id: &'a str, _error: &'a anyhow::Error) -> HandlerFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Called after the verification keys have been reloaded, so the handler can re-read its own
+    /// configuration.
+    fn on_reload(&self) -> HandlerFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Called once the server has stopped and in-flight verifications have finished.
+    fn on_shutdown(&self) -> HandlerFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+pub struct NoopHandler;
+
+impl ProofHandler for NoopHandler {}
+
+pub(crate) type ValidFn =
+    Box<dyn for<'a> Fn(&'a str, &'a [Fr]) -> HandlerFuture<'a, Verdict> + Send + Sync>;
+pub(crate) type InvalidFn =
+    Box<dyn for<'a> Fn(&'a str, &'a str) -> HandlerFuture<'a> + Send + Sync>;
+pub(crate) type ErrorFn =
+    Box<dyn for<'a> Fn(&'a str, &'a anyhow::Error) -> HandlerFuture<'a> + Send + Sync>;
+
+pub(crate) struct ValidProofAdapter {
+    pub(crate) inner: Box<dyn ProofHandler>,
+    pub(crate) f: ValidFn,
+}
+
+impl ProofHandler for ValidProofAdapter {
+    fn on_valid<'a>(
+        &'a self,
+        client_id: &'a str,
+        public_inputs: &'a [Fr],
+    ) -> HandlerFuture<'a, Verdict> {
+        (self.f)(client_id, public_inputs)
+    }
+
+    fn on_invalid<'a>(&'a self, client_id: &'a str, reason: &'a str) -> HandlerFuture<'a> {
+        self.inner.on_invalid(client_id, reason)
+    }
+
+    fn on_error<'a>(&'a self, client_id: &'a str, error: &'a anyhow::Error) -> HandlerFuture<'a> {
+        self.inner.on_error(client_id, error)
+    }
+
+    fn on_reload(&self) -> HandlerFuture<'_> {
+        self.inner.on_reload()
+    }
+
+    fn on_shutdown(&self) -> HandlerFuture<'_> {
+        self.inner.on_shutdown()
+    }
+}
+
+pub(crate) struct InvalidProofAdapter {
+    pub(crate) inner: Box<dyn ProofHandler>,
+    pub(crate) f: InvalidFn,
+}
+
+impl ProofHandler for InvalidProofAdapter {
+    fn on_valid<'a>(
+        &'a self,
+        client_id: &'a str,
+        public_inputs: &'a [Fr],
+    ) -> HandlerFuture<'a, Verdict> {
+        self.inner.on_valid(client_id, public_inputs)
+    }
+
+    fn on_invalid<'a>(&'a self, client_id: &'a str, reason: &'a str) -> HandlerFuture<'a> {
+        (self.f)(client_id, reason)
+    }
+
+    fn on_error<'a>(&'a self, client_id: &'a str, error: &'a anyhow::Error) -> HandlerFuture<'a> {
+        self.inner.on_error(client_id, error)
+    }
+
+    fn on_reload(&self) -> HandlerFuture<'_> {
+        self.inner.on_reload()
+    }
+
+    fn on_shutdown(&self) -> HandlerFuture<'_> {
+        self.inner.on_shutdown()
+    }
+}
+
+pub(crate) struct ErrorAdapter {
+    pub(crate) inner: Box<dyn ProofHandler>,
+    pub(crate) f: ErrorFn,
+}
+
+impl ProofHandler for ErrorAdapter {
+    fn on_valid<'a>(
+        &'a self,
+        client_id: &'a str,
+        public_inputs: &'a [Fr],
+    ) -> HandlerFuture<'a, Verdict> {
+        self.inner.on_valid(client_id, public_inputs)
+    }
+
+    fn on_invalid<'a>(&'a self, client_id: &'a str, reason: &'a str) -> HandlerFuture<'a> {
+        self.inner.on_invalid(client_id, reason)
+    }
+
+    fn on_error<'a>(&'a self, client_id: &'a str, error: &'a anyhow::Error) -> HandlerFuture<'a> {
+        (self.f)(client_id, error)
+    }
+
+    fn on_reload(&self) -> HandlerFuture<'_> {
+        self.inner.on_reload()
+    }
+
+    fn on_shutdown(&self) -> HandlerFuture<'_> {
+        self.inner.on_shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Default)]
+    struct Counts {
+        valid: AtomicUsize,
+        invalid: AtomicUsize,
+        errors: AtomicUsize,
+    }
+
+    struct CountingHandler(Arc<Counts>);
+
+    impl ProofHandler for CountingHandler {
+        fn on_valid<'a>(
+            &'a self,
+            _client_id: &'a str,
+            _public_inputs: &'a [Fr],
+        ) -> HandlerFuture<'a, Verdict> {
+            self.0.valid.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(Verdict::Accept { result: None }) })
+        }
+
+        fn on_invalid<'a>(&'a self, _client_id: &'a str, _reason: &'a str) -> HandlerFuture<'a> {
+            self.0.invalid.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn on_error<'a>(
+            &'a self,
+            _client_id: &'a str,
+            _error: &'a anyhow::Error,
+        ) -> HandlerFuture<'a> {
+            self.0.errors.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn noop_handler_accepts() {
+        let verdict = NoopHandler.on_valid("client", &[]).await.unwrap();
+        assert_eq!(verdict, Verdict::Accept { result: None });
+    }
+
+    #[tokio::test]
+    async fn adapters_only_override_their_method() {
+        let counts = Arc::new(Counts::default());
+        let handler = ValidProofAdapter {
+            inner: Box::new(CountingHandler(Arc::clone(&counts))),
+            f: Box::new(|_, _| Box::pin(async { Ok(Verdict::Retry) })),
+        };
+
+        assert_eq!(
+            handler.on_valid("client", &[]).await.unwrap(),
+            Verdict::Retry
+        );
+        handler.on_invalid("client", "bad").await.unwrap();
+        handler
+            .on_error("client", &anyhow::anyhow!("failed"))
+            .await
+            .unwrap();
+
+        assert_eq!(counts.valid.load(Ordering::SeqCst), 0);
+        assert_eq!(counts.invalid.load(Ordering::SeqCst), 1);
+        assert_eq!(counts.errors.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn stacked_adapters_keep_every_closure() {
+        let counts = Arc::new(Counts::default());
+        let invalid_calls = Arc::new(AtomicUsize::new(0));
+        let calls = Arc::clone(&invalid_calls);
+        let handler = ErrorAdapter {
+            inner: Box::new(InvalidProofAdapter {
+                inner: Box::new(CountingHandler(Arc::clone(&counts))),
+                f: Box::new(move |_, _| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Box::pin(async { Ok(()) })
+                }),
+            }),
+            f: Box::new(|_, _| Box::pin(async { Err(anyhow::anyhow!("handler failed")) })),
+        };
+
+        handler.on_valid("client", &[]).await.unwrap();
+        handler.on_invalid("client", "bad").await.unwrap();
+        assert!(handler
+            .on_error("client", &anyhow::anyhow!("failed"))
+            .await
+            .is_err());
+
+        assert_eq!(counts.valid.load(Ordering::SeqCst), 1);
+        assert_eq!(counts.invalid.load(Ordering::SeqCst), 0);
+        assert_eq!(counts.errors.load(Ordering::SeqCst), 0);
+        assert_eq!(invalid_calls.load(Ordering::SeqCst), 1);
+    }
+}
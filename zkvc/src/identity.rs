@@ -0,0 +1,158 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use log::debug;
+use rand::thread_rng;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::circuit::ProofRequest;
+
+/// Canonical message signed by a client: SHA-256 over the domain tag `zkvc-signature-v1`, the
+/// client id, the circuit id, the base64 proof, the number of public inputs, every public input
+/// and the nonce. Strings are prefixed with their length and numbers are encoded as little-endian
+/// `u64`; a missing nonce is encoded as an empty string.
+pub fn signing_message(request: &ProofRequest) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"zkvc-signature-v1");
+    update_string(&mut hasher, &request.client_id);
+    update_string(&mut hasher, &request.circuit_id);
+    update_string(&mut hasher, &request.proof.0);
+    hasher.update((request.public_inputs.len() as u64).to_le_bytes());
+    for input in &request.public_inputs {
+        update_string(&mut hasher, input);
+    }
+    update_string(&mut hasher, request.nonce.as_deref().unwrap_or_default());
+    hasher.finalize().into()
+}
+
+fn update_string(hasher: &mut Sha256, s: &str) {
+    hasher.update((s.len() as u64).to_le_bytes());
+    hasher.update(s.as_bytes());
+}
+
+pub(crate) fn sign_request(request: &mut ProofRequest, key: &SigningKey) {
+    let signature = key.sign(&signing_message(request));
+    request.signature = Some(STANDARD.encode(signature.to_bytes()));
+}
+
+pub(crate) fn verify_request(request: &ProofRequest, key: &VerifyingKey) -> Result<(), String> {
+    let encoded = request
+        .signature
+        .as_ref()
+        .ok_or_else(|| "Request is not signed".to_string())?;
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Signature is not valid base64: {}", e))?;
+    let signature = Signature::from_slice(&bytes).map_err(|e| format!("Bad signature: {}", e))?;
+    key.verify(&signing_message(request), &signature)
+        .map_err(|_| "Signature does not match the request".to_string())
+}
+
+pub fn generate_signing_key() -> SigningKey {
+    SigningKey::generate(&mut thread_rng())
+}
+
+/// Writes a new secret key to `path` and returns its public half.
+pub fn generate_signing_key_to_file(path: &Path) -> Result<VerifyingKey, anyhow::Error> {
+    let key = generate_signing_key();
+    std::fs::write(path, key.to_bytes())?;
+    debug!("Signing key saved to {}", path.display());
+    Ok(key.verifying_key())
+}
+
+pub fn load_signing_key(path: &Path) -> Result<SigningKey, anyhow::Error> {
+    let bytes = std::fs::read(path)?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Signing key in {} is not 32 bytes", path.display()))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Hex encoding of a public key, as used in `ServerConfig::client_keys`.
+pub fn public_key_to_hex(key: &VerifyingKey) -> String {
+    hex::encode(key.as_bytes())
+}
+
+pub fn public_key_from_hex(s: &str) -> Result<VerifyingKey, anyhow::Error> {
+    let bytes: [u8; 32] = hex::decode(s)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Public key is not 32 bytes"))?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::request;
+
+    fn signed_request(key: &SigningKey) -> ProofRequest {
+        let mut request = request("client", 3, 5).with_nonce("nonce");
+        request.sign(key);
+        request
+    }
+
+    #[test]
+    fn signature_verifies_with_the_signing_key() {
+        let key = generate_signing_key();
+        let request = signed_request(&key);
+        assert_eq!(verify_request(&request, &key.verifying_key()), Ok(()));
+    }
+
+    #[test]
+    fn signature_is_rejected_with_another_key() {
+        let request = signed_request(&generate_signing_key());
+        let other = generate_signing_key().verifying_key();
+        assert!(verify_request(&request, &other).is_err());
+    }
+
+    #[test]
+    fn signature_covers_every_field() {
+        let key = generate_signing_key();
+        let public_key = key.verifying_key();
+        let signed = signed_request(&key);
+
+        let changes: [fn(&mut ProofRequest); 7] = [
+            |request| request.client_id.push('x'),
+            |request| request.circuit_id.push('x'),
+            |request| request.public_inputs[0].push('0'),
+            |request| request.public_inputs.push("1".to_string()),
+            |request| request.nonce = Some("other".to_string()),
+            |request| request.nonce = None,
+            |request| request.proof.0 = request.proof.0.chars().rev().collect(),
+        ];
+        for change in changes {
+            let mut tampered = signed.clone();
+            change(&mut tampered);
+            assert!(verify_request(&tampered, &public_key).is_err());
+        }
+    }
+
+    #[test]
+    fn unsigned_and_garbled_signatures_are_rejected() {
+        let key = generate_signing_key();
+        let mut request = signed_request(&key);
+        request.signature = None;
+        assert!(verify_request(&request, &key.verifying_key()).is_err());
+        request.signature = Some("not base64!".to_string());
+        assert!(verify_request(&request, &key.verifying_key()).is_err());
+        request.signature = Some(STANDARD.encode([0u8; 10]));
+        assert!(verify_request(&request, &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn keys_round_trip_through_files_and_hex() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("client.key");
+        let public_key = generate_signing_key_to_file(&path).unwrap();
+        let key = load_signing_key(&path).unwrap();
+        assert_eq!(key.verifying_key(), public_key);
+        assert_eq!(
+            public_key_from_hex(&public_key_to_hex(&public_key)).unwrap(),
+            public_key
+        );
+
+        std::fs::write(&path, [0u8; 31]).unwrap();
+        assert!(load_signing_key(&path).is_err());
+        assert!(public_key_from_hex("abcd").is_err());
+    }
+}
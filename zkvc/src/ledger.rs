@@ -0,0 +1,302 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use crate::response::VerificationResponse;
+
+/// A single submission as seen by the server, together with the answer it received.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub client_id: String,
+    pub circuit_id: String,
+    pub public_inputs: Vec<String>,
+    /// Base64 proof bytes exactly as submitted.
+    pub proof: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub verdict: VerificationResponse,
+    #[serde(with = "unix_millis")]
+    pub received_at: SystemTime,
+    #[serde(with = "unix_millis")]
+    pub decided_at: SystemTime,
+    /// Fingerprint of the verification key the proof was checked against, if the circuit is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_fingerprint: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LedgerQuery {
+    pub client_id: Option<String>,
+    pub circuit_id: Option<String>,
+    pub since: Option<SystemTime>,
+    pub until: Option<SystemTime>,
+    /// Keep only the most recent `limit` matching entries.
+    pub limit: Option<usize>,
+}
+
+impl LedgerQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    pub fn circuit(mut self, circuit_id: impl Into<String>) -> Self {
+        self.circuit_id = Some(circuit_id.into());
+        self
+    }
+
+    pub fn between(mut self, since: SystemTime, until: SystemTime) -> Self {
+        self.since = Some(since);
+        self.until = Some(until);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, entry: &LedgerEntry) -> bool {
+        self.client_id
+            .as_ref()
+            .is_none_or(|client_id| *client_id == entry.client_id)
+            && self
+                .circuit_id
+                .as_ref()
+                .is_none_or(|circuit_id| *circuit_id == entry.circuit_id)
+            && self.since.is_none_or(|since| entry.received_at >= since)
+            && self.until.is_none_or(|until| entry.received_at < until)
+    }
+}
+
+/// Durable record of every submission the server has answered.
+///
+/// `ServerApp` calls these methods on the blocking thread pool and answers a submission only after
+/// `record` has returned, so implementations may block on I/O.
+pub trait Ledger: Send + Sync + 'static {
+    /// Returns once the entry is stored durably enough to survive a crash of the server.
+    fn record(&self, entry: &LedgerEntry) -> Result<(), anyhow::Error>;
+
+    /// Matching entries in the order they were recorded.
+    fn query(&self, query: &LedgerQuery) -> Result<Vec<LedgerEntry>, anyhow::Error>;
+
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+/// Ledger stored as one JSON object per line in an append-only file. Every entry is synced to disk
+/// before `record` returns.
+pub struct FileLedger {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileLedger {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Ledger for FileLedger {
+    fn record(&self, entry: &LedgerEntry) -> Result<(), anyhow::Error> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn query(&self, query: &LedgerQuery) -> Result<Vec<LedgerEntry>, anyhow::Error> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut entries = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<LedgerEntry>(&line) {
+                Ok(entry) if query.matches(&entry) => entries.push(entry),
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "Skipping unreadable ledger line {} in {}: {}",
+                    number + 1,
+                    self.path.display(),
+                    e
+                ),
+            }
+        }
+
+        if let Some(limit) = query.limit {
+            let skip = entries.len().saturating_sub(limit);
+            entries.drain(..skip);
+        }
+        Ok(entries)
+    }
+
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        self.file.lock().unwrap().sync_data()?;
+        Ok(())
+    }
+}
+
+pub(crate) mod unix_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub(crate) fn serialize<S: Serializer>(
+        time: &SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let millis = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(serde::ser::Error::custom)?
+            .as_millis();
+        serializer.serialize_u64(millis as u64)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SystemTime, D::Error> {
+        let millis = u64::deserialize(deserializer)?;
+        Ok(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn entry(client_id: &str, circuit_id: &str, received_secs: u64) -> LedgerEntry {
+        let received_at = UNIX_EPOCH + Duration::from_secs(received_secs);
+        LedgerEntry {
+            client_id: client_id.to_string(),
+            circuit_id: circuit_id.to_string(),
+            public_inputs: vec!["15".to_string()],
+            proof: "cHJvb2Y=".to_string(),
+            nonce: Some("nonce".to_string()),
+            verdict: VerificationResponse::Valid { result: None },
+            received_at,
+            decided_at: received_at + Duration::from_millis(5),
+            key_fingerprint: Some("fingerprint".to_string()),
+        }
+    }
+
+    fn clients(entries: &[LedgerEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.client_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = FileLedger::open(dir.path().join("ledger.jsonl")).unwrap();
+        let recorded = entry("a", "default", 10);
+        ledger.record(&recorded).unwrap();
+
+        let entries = ledger.query(&LedgerQuery::new()).unwrap();
+        assert_eq!(entries.len(), 1);
+        let read = &entries[0];
+        assert_eq!(read.client_id, recorded.client_id);
+        assert_eq!(read.public_inputs, recorded.public_inputs);
+        assert_eq!(read.proof, recorded.proof);
+        assert_eq!(read.nonce, recorded.nonce);
+        assert!(matches!(read.verdict, VerificationResponse::Valid { .. }));
+        assert_eq!(read.received_at, recorded.received_at);
+        assert_eq!(read.decided_at, recorded.decided_at);
+        assert_eq!(read.key_fingerprint, recorded.key_fingerprint);
+    }
+
+    #[test]
+    fn entries_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.jsonl");
+        {
+            let ledger = FileLedger::open(&path).unwrap();
+            ledger.record(&entry("a", "default", 10)).unwrap();
+        }
+        let ledger = FileLedger::open(&path).unwrap();
+        ledger.record(&entry("b", "default", 20)).unwrap();
+
+        let entries = ledger.query(&LedgerQuery::new()).unwrap();
+        assert_eq!(clients(&entries), ["a", "b"]);
+    }
+
+    #[test]
+    fn query_filters_by_time_range_client_and_circuit() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = FileLedger::open(dir.path().join("ledger.jsonl")).unwrap();
+        for recorded in [
+            entry("a", "default", 10),
+            entry("b", "default", 20),
+            entry("a", "other", 30),
+            entry("a", "default", 40),
+        ] {
+            ledger.record(&recorded).unwrap();
+        }
+
+        let between = LedgerQuery::new().between(
+            UNIX_EPOCH + Duration::from_secs(20),
+            UNIX_EPOCH + Duration::from_secs(40),
+        );
+        let entries = ledger.query(&between).unwrap();
+        assert_eq!(clients(&entries), ["b", "a"]);
+
+        let entries = ledger
+            .query(&LedgerQuery::new().client("a").circuit("default"))
+            .unwrap();
+        let times: Vec<_> = entries.iter().map(|entry| entry.received_at).collect();
+        assert_eq!(
+            times,
+            [
+                UNIX_EPOCH + Duration::from_secs(10),
+                UNIX_EPOCH + Duration::from_secs(40)
+            ]
+        );
+
+        let entries = ledger.query(&LedgerQuery::new().limit(2)).unwrap();
+        assert_eq!(entries[0].circuit_id, "other");
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.jsonl");
+        let ledger = FileLedger::open(&path).unwrap();
+        ledger.record(&entry("a", "default", 10)).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"truncated\n")
+            .unwrap();
+        ledger.record(&entry("b", "default", 20)).unwrap();
+
+        let entries = ledger.query(&LedgerQuery::new()).unwrap();
+        assert_eq!(clients(&entries), ["a", "b"]);
+    }
+}
@@ -23,6 +23,7 @@
 //! First, define your computation as a circuit by implementing the `ConstraintGenerator` trait:
 //!
 //! ```rust
+//! # use ark_r1cs_std::eq::EqGadget;
 //! use ark_bls12_381::Fr;
 //! use ark_relations::r1cs::SynthesisError;
 //! use zkvc::circuit::{ConstraintGenerator, ZkCircuitContext};
@@ -56,7 +57,19 @@
 //!
 //! Generate proving and verification keys for your circuit:
 //!
-//! ```rust
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use ark_relations::r1cs::SynthesisError;
+//! # use zkvc::circuit::{ConstraintGenerator, ZkCircuitContext};
+//! # struct MyCircuit {
+//! #     private_input: u64,
+//! #     public_input: u64,
+//! # }
+//! # impl ConstraintGenerator<Fr> for MyCircuit {
+//! #     fn generate_constraints(&self, _: &mut ZkCircuitContext<Fr>) -> Result<(), SynthesisError> {
+//! #         Ok(())
+//! #     }
+//! # }
 //! use std::path::PathBuf;
 //! use zkvc::setup;
 //!
@@ -70,19 +83,19 @@
 //!     &PathBuf::from("pk.bin"),
 //!     &PathBuf::from("vk.bin"),
 //! )?;
+//! # Ok::<(), anyhow::Error>(())
 //! ```
 //!
 //! ### 3. Server Implementation
 //!
 //! Create a server that verifies proofs from clients:
 //!
-//! ```rust
+//! ```rust,no_run
+//! # use std::path::PathBuf;
+//! # async fn example() -> Result<(), anyhow::Error> {
 //! use zkvc::server::{ServerApp, ServerConfig};
 //!
-//! let config = ServerConfig {
-//!     listen_address: "127.0.0.1:65432".to_string(),
-//!     verification_key_path: PathBuf::from("vk.bin"),
-//! };
+//! let config = ServerConfig::new("127.0.0.1:65432", PathBuf::from("vk.bin"));
 //!
 //! let server = ServerApp::new(config)?
 //!     .with_valid_proof_handler(|client_id, public_inputs| {
@@ -99,21 +112,430 @@
 //!     });
 //!
 //! server.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Handlers that need shared state can be grouped into a type implementing `ProofHandler`
+//! instead. All of its methods have no-op defaults and return boxed futures, which the server
+//! awaits before answering the client. `on_valid` returns a `Verdict`, so a handler can still
+//! reject a cryptographically valid proof or ask the client to retry later:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use zkvc::server::{ServerApp, ServerConfig};
+//! # fn example(config: ServerConfig) -> Result<(), anyhow::Error> {
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use zkvc::handler::{HandlerFuture, ProofHandler, Verdict};
+//!
+//! struct LimitedHandler {
+//!     accepted: AtomicUsize,
+//!     limit: usize,
+//! }
+//!
+//! impl ProofHandler for LimitedHandler {
+//!     fn on_valid<'a>(
+//!         &'a self,
+//!         client_id: &'a str,
+//!         _public_inputs: &'a [Fr],
+//!     ) -> HandlerFuture<'a, Verdict> {
+//!         Box::pin(async move {
+//!             let total = self.accepted.fetch_add(1, Ordering::SeqCst) + 1;
+//!             if total > self.limit {
+//!                 return Ok(Verdict::Reject {
+//!                     reason: "Enough results collected".to_string(),
+//!                 });
+//!             }
+//!             println!("Client {} provided proof number {}", client_id, total);
+//!             Ok(Verdict::Accept { result: None })
+//!         })
+//!     }
+//! }
+//!
+//! let server = ServerApp::new(config)?.with_handler(LimitedHandler {
+//!     accepted: AtomicUsize::new(0),
+//!     limit: 100,
+//! });
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `with_handler` replaces any closures registered before it, so register a handler first and add
+//! closures afterwards to override single methods.
+//!
+//! Closures that need to await I/O can be registered with the `with_async_*_handler` builders,
+//! which pass owned arguments:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use zkvc::server::{ServerApp, ServerConfig};
+//! # async fn store_result(_: &str, _: &[Fr]) -> Result<(), anyhow::Error> {
+//! #     Ok(())
+//! # }
+//! # fn example(config: ServerConfig) -> Result<(), anyhow::Error> {
+//! let server = ServerApp::new(config)?.with_async_valid_proof_handler(|client_id, public_inputs| async move {
+//!     store_result(&client_id, &public_inputs).await?;
+//!     Ok(())
+//! });
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A single server can verify proofs for several circuits. Each circuit is registered under an id
+//! with its own verification key and, optionally, its own handler. Clients select the circuit
+//! through `ClientConfig::circuit_id`; requests without one use `DEFAULT_CIRCUIT_ID`:
+//!
+//! ```rust,no_run
+//! # use std::path::PathBuf;
+//! # use zkvc::{handler::ProofHandler, server::{ServerApp, ServerConfig}};
+//! # #[derive(Default)]
+//! # struct FactorizationHandler;
+//! # impl ProofHandler for FactorizationHandler {}
+//! # #[derive(Default)]
+//! # struct MatrixHandler;
+//! # impl ProofHandler for MatrixHandler {}
+//! # fn example() -> Result<(), anyhow::Error> {
+//! let config = ServerConfig::new("127.0.0.1:65432", PathBuf::from("fvk.bin"))
+//!     .with_circuit("matrix", PathBuf::from("mvk.bin"));
+//!
+//! let server = ServerApp::new(config)?
+//!     .with_handler(FactorizationHandler::default())
+//!     .with_circuit_handler("matrix", MatrixHandler::default());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Servers that hand out work can register a `ChallengeProvider`. The current challenge is served
+//! at `/challenge` next to `/verify`, valid proofs that do not answer it are rejected, and the
+//! challenge is rotated once a proof consuming it has been accepted:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use std::sync::Mutex;
+//! # use zkvc::server::{ServerApp, ServerConfig};
+//! # fn generate_product() -> u64 {
+//! #     15
+//! # }
+//! # fn example(config: ServerConfig) -> Result<(), anyhow::Error> {
+//! use zkvc::challenge::ChallengeProvider;
+//!
+//! struct ProductChallenge {
+//!     product: Mutex<u64>,
+//! }
+//!
+//! impl ChallengeProvider for ProductChallenge {
+//!     type Challenge = u64;
+//!
+//!     fn issue(&self) -> u64 {
+//!         *self.product.lock().unwrap()
+//!     }
+//!
+//!     fn rotate(&self) {
+//!         *self.product.lock().unwrap() = generate_product();
+//!     }
+//!
+//!     fn validate(&self, public_inputs: &[Fr]) -> bool {
+//!         public_inputs.first() == Some(&Fr::from(*self.product.lock().unwrap()))
+//!     }
+//!
+//!     fn expected_inputs(&self) -> Option<Vec<Fr>> {
+//!         Some(vec![Fr::from(*self.product.lock().unwrap())])
+//!     }
+//! }
+//!
+//! let server = ServerApp::new(config)?.with_challenge_provider(ProductChallenge {
+//!     product: Mutex::new(generate_product()),
+//! });
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Clients fetch it with `ClientApp::fetch_challenge`.
+//!
+//! Trust rules on the public inputs can be declared as an `InputPolicy`. The rules are checked right
+//! after the pairing check, and a violating proof is answered with
+//! `VerificationResponse::Rejected` carrying the violated rule. `require_challenge` compares inputs
+//! with `ChallengeProvider::expected_inputs` of the circuit's challenge, which cannot rotate while
+//! the rules are checked:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use zkvc::server::{ServerApp, ServerConfig};
+//! # fn example(config: ServerConfig) -> Result<(), anyhow::Error> {
+//! use zkvc::policy::InputPolicy;
+//!
+//! let server = ServerApp::new(config)?.with_input_policy(
+//!     InputPolicy::new()
+//!         .require_count(4)
+//!         .require_equal(0..2, vec![Fr::from(1u64), Fr::from(2u64)])
+//!         .require_one_of(3, vec![Fr::from(10u64), Fr::from(20u64)])
+//!         .require_challenge(2..3),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Accepted submissions are remembered for `ServerConfig::replay_retention_secs`, keyed by a
+//! digest of the circuit id and the public inputs. Resubmissions within that window are answered
+//! with `VerificationResponse::Duplicate`, whichever client sends them. Requests may also carry a
+//! single-use nonce issued at `/nonce`, which is valid for `ServerConfig::nonce_ttl_secs` and used
+//! up once a proof carrying it is accepted. Set `ServerConfig::require_nonce` to refuse requests
+//! without a nonce, and use `ServerApp::with_seen_set` to keep the seen-set somewhere other than in
+//! memory. At most `ServerConfig::max_outstanding_nonces` nonces are valid at once, and at most
+//! `ServerConfig::max_nonces_per_peer` of them for one peer address. `/nonce` is also rate limited
+//! per peer address with the `per_ip` settings of `ServerConfig::rate_limit`, when those are set.
+//!
+//! Verification keys are prepared once when the server starts. Besides `/verify`, the server
+//! accepts a JSON array of proof requests at `/verify/batch` (up to
+//! `ServerConfig::max_batch_size`). Proofs for the same circuit are checked together with a single
+//! randomized pairing check, falling back to per-proof checks when it fails, and the response
+//! lists one `VerificationResponse` per request in the same order.
+//!
+//! Requests are validated before any pairing check. Bodies over `ServerConfig::max_request_bytes`,
+//! unreadable JSON, bad base64, proofs of the wrong length or not on the curve, a public input count
+//! that does not match the verification key and non-canonical field encodings are all answered
+//! with `VerificationResponse::Malformed`, whose `MalformedReason` says what was wrong. The status
+//! is HTTP 400, or 413 for bodies and batches that are too large.
+//!
+//! Client ids can be bound to ed25519 keys. A client configured with
+//! `ClientConfig::signing_key_path` signs the circuit id, proof, public inputs and nonce of every
+//! request (see `identity::signing_message`). The server checks the signature of every request
+//! naming a client registered in `ServerConfig::client_keys` or with `ServerApp::with_client_key`
+//! and answers forged or unsigned ones with `VerificationResponse::Unauthorized` (HTTP 401) before
+//! any verification. Set `ServerConfig::require_signatures` to refuse unregistered clients too:
+//!
+//! ```rust,no_run
+//! # use std::path::{Path, PathBuf};
+//! # use url::Url;
+//! # use zkvc::{client::{ClientApp, ClientConfig}, server::{ServerApp, ServerConfig}};
+//! # fn example(config: ServerConfig, server_url: Url) -> Result<(), anyhow::Error> {
+//! use zkvc::identity;
+//!
+//! let public_key = identity::generate_signing_key_to_file(Path::new("client.key"))?;
+//!
+//! let server = ServerApp::new(config)?.with_client_key("client-1", public_key);
+//!
+//! let client = ClientApp::new(ClientConfig {
+//!     signing_key_path: Some(PathBuf::from("client.key")),
+//!     ..ClientConfig::new(server_url, PathBuf::from("pk.bin"), "client-1")
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To serve over HTTPS, set `ServerConfig::tls` to a PEM certificate chain and key. Setting
+//! `TlsConfig::client_ca_path` as well makes the server require client certificates issued by those
+//! CAs. On the client side, `ClientConfig::ca_cert_path` adds trust roots (for example for a
+//! self-signed server certificate), and `client_cert_path` / `client_key_path` provide the client
+//! certificate:
+//!
+//! ```rust,no_run
+//! # use std::path::PathBuf;
+//! # use url::Url;
+//! # use zkvc::{client::{ClientApp, ClientConfig}, server::ServerConfig};
+//! # fn example() -> Result<(), anyhow::Error> {
+//! use zkvc::tls::TlsConfig;
+//!
+//! let mut config = ServerConfig::new("127.0.0.1:65432", PathBuf::from("vk.bin"));
+//! config.tls = Some(TlsConfig {
+//!     cert_path: PathBuf::from("server.pem"),
+//!     key_path: PathBuf::from("server.key"),
+//!     client_ca_path: Some(PathBuf::from("ca.pem")),
+//! });
+//!
+//! let client = ClientApp::new(ClientConfig {
+//!     ca_cert_path: Some(PathBuf::from("ca.pem")),
+//!     client_cert_path: Some(PathBuf::from("client-tls.pem")),
+//!     client_key_path: Some(PathBuf::from("client-tls.key")),
+//!     ..ClientConfig::new(Url::parse("https://localhost:65432")?, PathBuf::from("pk.bin"), "client-1")
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `ServerConfig::rate_limit` guards the verification endpoints before any decoding happens:
+//! token buckets per client id and per peer address answer excess submissions with
+//! `VerificationResponse::RateLimited` (HTTP 429), and clients or addresses that keep sending
+//! invalid proofs are banned for a while with `VerificationResponse::Banned` (HTTP 403). Both carry
+//! a retry delay so honest clients can back off. The per-client bucket, the client allow list and
+//! strikes against a client id only apply to requests signed with that client's registered key;
+//! everything else is limited and banned by peer address alone:
+//!
+//! ```rust
+//! # use std::path::PathBuf;
+//! # use zkvc::server::ServerConfig;
+//! use zkvc::limit::TokenBucketConfig;
+//!
+//! let mut config = ServerConfig::new("127.0.0.1:65432", PathBuf::from("vk.bin"));
+//! config.rate_limit.per_client = Some(TokenBucketConfig {
+//!     capacity: 10,
+//!     refill_per_sec: 1.0,
+//! });
+//! config.rate_limit.ban_after_invalid = Some(20);
+//! config.rate_limit.deny_clients.insert("client-13".to_string());
+//! ```
+//!
+//! Pairing checks run on the blocking thread pool, at most `ServerConfig::verify_concurrency` at a
+//! time with up to `ServerConfig::verify_queue_depth` more waiting. Requests beyond that are
+//! answered with `VerificationResponse::Retry` (HTTP 503 with `Retry-After` set to
+//! `ServerConfig::verify_retry_after_secs`).
+//!
+//! Set `ServerConfig::ledger_path` to record every answered submission (client id, public inputs,
+//! proof, verdict, timestamps and verification key fingerprint) as JSON lines, or plug in another
+//! store with `ServerApp::with_ledger`. Entries are written on the blocking thread pool before the
+//! submission is answered, and `FileLedger` syncs each one to disk, so every answer the server has
+//! sent survives a crash. Recorded entries can be read back with `Ledger::query`:
+//!
+//! ```rust,no_run
+//! use zkvc::ledger::{FileLedger, Ledger, LedgerQuery};
+//!
+//! let ledger = FileLedger::open("ledger.jsonl")?;
+//! let recent = ledger.query(&LedgerQuery::new().client("client-1").limit(20))?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! `/info` describes the server: the protocol version, the curve, and for every registered circuit
+//! the fingerprint of its verification key and the number of public inputs it expects. Clients can
+//! call `ClientApp::check_server_info` before proving to make sure their proving key matches.
+//!
+//! With `ServerConfig::metrics_enabled` set, the server exposes Prometheus metrics at `/metrics`:
+//! submissions per outcome, client and circuit, decoding and pairing-check latency, verification
+//! queue depth and request sizes. By default only clients authenticated by a signature get a label
+//! of their own, so per-client metrics need `ServerConfig::client_keys`. Other submissions are
+//! counted under `unauthenticated`, and those for unregistered circuits under `unknown`, so made-up
+//! ids cannot grow the metrics without bound. Deployments without signatures can set
+//! `ServerConfig::metrics_claimed_client_labels` to label that many claimed, unverified ids as well.
+//!
+//! `ServerApp::run` serves until the process is interrupted. To control the server's lifetime, use
+//! `ServerApp::start`, which returns a `ServerHandle` with the bound address (useful when listening
+//! on port 0). Stopping it drains in-flight verifications, flushes the ledger and calls
+//! `ProofHandler::on_shutdown`:
+//!
+//! ```rust,no_run
+//! # use std::path::PathBuf;
+//! # use zkvc::server::{ServerApp, ServerConfig};
+//! # async fn example() -> Result<(), anyhow::Error> {
+//! let handle = ServerApp::new(ServerConfig::new("127.0.0.1:0", PathBuf::from("vk.bin")))?.start()?;
+//! println!("Listening on {}", handle.local_addr());
+//!
+//! handle.stop(true).await;
+//! handle.join().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To serve the verifier from an application you already run, turn it into a `VerifierService`
+//! and mount it under a prefix. Your own middleware applies to its routes, and clients point
+//! `server_url` at the prefix (e.g. `http://127.0.0.1:8080/zkvc/`):
+//!
+//! ```rust,no_run
+//! # use actix_web::{middleware::Logger, web, App, HttpResponse, HttpServer};
+//! # use zkvc::server::{ServerApp, ServerConfig};
+//! # async fn index() -> HttpResponse {
+//! #     HttpResponse::Ok().finish()
+//! # }
+//! # async fn example(config: ServerConfig) -> Result<(), anyhow::Error> {
+//! let service = ServerApp::new(config)?.into_service();
+//! let routes = service.clone();
+//! HttpServer::new(move || {
+//!     App::new()
+//!         .wrap(Logger::default())
+//!         .service(routes.scope("/zkvc"))
+//!         .route("/", web::get().to(index))
+//! })
+//! .bind("127.0.0.1:8080")?
+//! .run()
+//! .await?;
+//! service.shutdown().await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Verification keys can be swapped without a restart. Set
+//! `ServerConfig::key_reload_interval_secs` to re-read the key files periodically, or set
+//! `ServerConfig::admin_token` and send `POST /admin/reload` with that bearer token. Changed keys
+//! are prepared before being swapped in, proofs for the replaced key are still accepted for
+//! `ServerConfig::key_grace_secs`, and handlers are told through `ProofHandler::on_reload`.
+//!
+//! The same token guards the rest of the `/admin` scope, which lets operators inspect and control
+//! a running server. Every admin request, including refused ones, is logged as an audit event
+//! under the `zkvc::audit` target:
+//!
+//! | Route | Effect |
+//! |---|---|
+//! | `GET /admin/status` | Pause state, pending verifications and revoked clients |
+//! | `POST /admin/pause`, `POST /admin/resume` | Answer submissions with `Retry` while paused |
+//! | `GET /admin/clients` | Valid, invalid and rejected counts per signed client, with ban state; unsigned submissions count as `unauthenticated` |
+//! | `GET /admin/submissions?client_id=&circuit_id=&since=&limit=` | Recent ledger entries |
+//! | `POST /admin/clients/{id}/ban?duration_secs=` | Ban a client, even an allow-listed one |
+//! | `POST /admin/clients/{id}/revoke` | Refuse a client until it is unbanned |
+//! | `POST /admin/clients/{id}/unban` | Lift a ban or revocation |
+//! | `POST /admin/challenges/{circuit_id}/rotate` | Move a challenge on |
+//!
+//! The verification pipeline itself does not depend on HTTP. A `Verifier` takes a `VerifierConfig`
+//! (keys, replay protection, signatures and the verification queue; `ServerConfig` converts into
+//! one) and the same handlers as `ServerApp`, and answers plain `ProofRequest` values, so it can be
+//! driven from a queue consumer, a CLI or a test. Rate limits, the ledger and metrics stay with
+//! `ServerApp`:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::{Bls12_381, Fr};
+//! # use ark_groth16::Proof;
+//! # use zkvc::{circuit::DEFAULT_CIRCUIT_ID, handler::ProofHandler, response::VerificationResponse};
+//! # #[derive(Default)]
+//! # struct MyHandler;
+//! # impl ProofHandler for MyHandler {}
+//! # async fn example(
+//! #     proof: Proof<Bls12_381>,
+//! #     public_inputs: Vec<Fr>,
+//! # ) -> Result<(), anyhow::Error> {
+//! use zkvc::{
+//!     circuit::ProofRequest,
+//!     verifier::{Verifier, VerifierConfig},
+//! };
+//!
+//! let config = VerifierConfig::new("vk.bin");
+//! let verifier = Verifier::new(&config)?.with_handler(MyHandler::default());
+//!
+//! let request = ProofRequest::from_proof("client-1", DEFAULT_CIRCUIT_ID, &proof, &public_inputs)?
+//!     .with_nonce(verifier.issue_nonce()?);
+//! match verifier.verify(&request).await {
+//!     VerificationResponse::Valid { .. } => println!("Accepted"),
+//!     other => println!("Refused: {:?}", other),
+//! }
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ### 4. Client Implementation
 //!
 //! Create a client that generates and sends proofs:
 //!
-//! ```rust
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use ark_relations::r1cs::SynthesisError;
+//! # use zkvc::circuit::{ConstraintGenerator, ZkCircuitContext};
+//! # struct MyCircuit {
+//! #     private_input: u64,
+//! #     public_input: u64,
+//! # }
+//! # impl ConstraintGenerator<Fr> for MyCircuit {
+//! #     fn generate_constraints(&self, _: &mut ZkCircuitContext<Fr>) -> Result<(), SynthesisError> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # use std::path::PathBuf;
+//! # async fn example() -> Result<(), anyhow::Error> {
 //! use zkvc::client::{ClientApp, ClientConfig};
 //! use url::Url;
 //!
 //! let config = ClientConfig {
-//!     server_url: Url::parse("http://127.0.0.1:65432")?,
-//!     proving_key_path: PathBuf::from("pk.bin"),
 //!     proof_path: Some(PathBuf::from("proof.json")),
-//!     client_id: "client-1".to_string(),
+//!     ..ClientConfig::new(
+//!         Url::parse("http://127.0.0.1:65432")?,
+//!         PathBuf::from("pk.bin"),
+//!         "client-1",
+//!     )
 //! };
 //!
 //! let client = ClientApp::new(config)?;
@@ -124,6 +546,110 @@
 //! };
 //!
 //! let response = client.generate_and_send_proof(Box::new(circuit)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Proving and submitting can also happen separately, for example on an air-gapped machine that
+//! holds the proving key and a connected host that only holds the signing key. `ClientApp::prove`
+//! never contacts the server, the proving key is only read when it is first needed, and
+//! `ClientApp::submit` adds the nonce and signature when the proof is sent:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use ark_relations::r1cs::SynthesisError;
+//! # use zkvc::circuit::{ConstraintGenerator, ZkCircuitContext};
+//! # struct MyCircuit {
+//! #     private_input: u64,
+//! #     public_input: u64,
+//! # }
+//! # impl ConstraintGenerator<Fr> for MyCircuit {
+//! #     fn generate_constraints(&self, _: &mut ZkCircuitContext<Fr>) -> Result<(), SynthesisError> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # use zkvc::{circuit::ProofRequest, client::ClientApp};
+//! # async fn example(client: ClientApp, circuit: MyCircuit) -> Result<(), anyhow::Error> {
+//! // On the proving machine
+//! let request = client.prove(Box::new(circuit)).await?;
+//! request.save("proof.json")?;
+//!
+//! // On the submitting host
+//! let response = client.submit(ProofRequest::load("proof.json")?).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Proofs are generated on tokio's blocking thread pool, so the runtime stays responsive while a
+//! long proof runs. Arkworks parallelises proving over rayon's global pool by default; set
+//! `ClientConfig::prover_threads` to give proving a dedicated pool of that size instead.
+//!
+//! Because the generator is moved to another thread, `ConstraintGenerator` now requires `Send`.
+//! This is a breaking change: a circuit holding an `Rc` or another non-`Send` value no longer
+//! compiles and has to own its data or switch to `Arc`.
+//!
+//! Set `ClientConfig::outbox` to keep every proof in a directory until the server has answered it.
+//! Unreachable servers and `Retry` / `RateLimited` answers are retried with exponential backoff and
+//! jitter, always with the stored nonce and signature, so a proof whose answer got lost comes back
+//! as `VerificationResponse::Duplicate` instead of being counted twice. Proofs whose nonce expired
+//! while they waited are answered with `VerificationResponse::StaleNonce`, which does not count
+//! against the client, and are sent again with a fresh nonce. Proofs left over from an earlier run
+//! are resubmitted by `ClientApp::resume_outbox`, which `run_worker` calls on start:
+//!
+//! ```rust,no_run
+//! # use std::path::PathBuf;
+//! # use url::Url;
+//! # use zkvc::client::{ClientApp, ClientConfig};
+//! # async fn example(server_url: Url) -> Result<(), anyhow::Error> {
+//! use zkvc::outbox::OutboxConfig;
+//!
+//! let client = ClientApp::new(ClientConfig {
+//!     outbox: Some(OutboxConfig::new("outbox")),
+//!     ..ClientConfig::new(server_url, PathBuf::from("pk.bin"), "client-1")
+//! })?;
+//! client.resume_outbox().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A volunteer usually keeps working instead: `ClientApp::run_worker` takes tasks from a
+//! `TaskSource` (the server's challenges with `ChallengeSource`, or any iterator with `IterSource`),
+//! builds a circuit for each with the given factory, proves and submits it, and repeats. It backs
+//! off by `ClientConfig::worker` when there is no work or the server asks it to wait, and stops
+//! after `WorkerConfig::max_tasks` tasks or once the stop signal fires:
+//!
+//! ```rust,no_run
+//! # use ark_bls12_381::Fr;
+//! # use ark_relations::r1cs::SynthesisError;
+//! # use zkvc::{circuit::{ConstraintGenerator, ZkCircuitContext}, client::{ClientApp, ClientConfig}};
+//! # struct FactorizationCircuit {
+//! #     p1: u64,
+//! #     p2: u64,
+//! #     product: u64,
+//! # }
+//! # impl ConstraintGenerator<Fr> for FactorizationCircuit {
+//! #     fn generate_constraints(&self, _: &mut ZkCircuitContext<Fr>) -> Result<(), SynthesisError> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # fn factor(product: u64) -> Result<(u64, u64), anyhow::Error> {
+//! #     Ok((1, product))
+//! # }
+//! # async fn example(config: ClientConfig) -> Result<(), anyhow::Error> {
+//! use tokio::sync::watch;
+//! use zkvc::worker::ChallengeSource;
+//!
+//! let (stop_tx, stop_rx) = watch::channel(false);
+//! let client = ClientApp::new(config)?.with_stop_signal(stop_rx);
+//!
+//! let stats = client
+//!     .run_worker(ChallengeSource::<u64>::new(), |product| {
+//!         let (p1, p2) = factor(product)?;
+//!         Ok(Box::new(FactorizationCircuit { p1, p2, product }))
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ## Arkworks Gadgets
@@ -131,15 +657,24 @@
 //! The library supports Arkworks gadgets for complex operations. Here's an example of using MiMC hash:
 //!
 //! ```rust
+//! # use ark_bls12_381::Fr;
+//! # use ark_crypto_primitives::crh::constraints::TwoToOneCRHGadget;
+//! # use ark_r1cs_std::fields::fp::FpVar;
+//! # use ark_relations::r1cs::SynthesisError;
+//! # use zkvc::circuit::ZkCircuitContext;
+//! # fn hash_value(context: &mut ZkCircuitContext<Fr>) -> Result<(), SynthesisError> {
 //! use ark_r1cs_std::ToBytesGadget;
 //! use arkworks_mimc::{
 //!     constraints::{MiMCNonFeistelCRHGadget, MiMCVar},
-//!     params::mimc_7_91_bls12_381::{MIMC_7_91_BLS12_381_PARAMS, MIMC_7_91_BLS12_381_ROUND_KEYS},
+//!     params::{
+//!         mimc_7_91_bls12_381::{MIMC_7_91_BLS12_381_PARAMS, MIMC_7_91_BLS12_381_ROUND_KEYS},
+//!         round_keys_contants_to_vec,
+//!     },
 //! };
 //!
 //! // Inside your circuit implementation:
 //! let public_zero = context.new_witness(|| Ok(Fr::from(0u64)))?;
-//! let public_round_keys = MIMC_7_91_BLS12_381_ROUND_KEYS
+//! let public_round_keys = round_keys_contants_to_vec(&MIMC_7_91_BLS12_381_ROUND_KEYS)
 //!     .iter()
 //!     .map(|x| context.new_witness(|| Ok(*x)))
 //!     .collect::<Result<Vec<_>, _>>()?;
@@ -156,11 +691,30 @@
 //!     &FpVar::<Fr>::Constant(Fr::from(0u64)).to_bytes()?,
 //!     &value.to_bytes()?,
 //! )?;
+//! # Ok(())
+//! # }
 //! ```
 
+pub mod admin;
+mod batch;
+pub mod challenge;
 pub mod circuit;
 pub mod client;
+pub mod handler;
+pub mod identity;
+pub mod ledger;
+pub mod limit;
+mod metrics;
+pub mod outbox;
+pub mod policy;
+mod pool;
+pub mod replay;
 pub mod response;
 pub mod server;
 pub mod setup;
+#[cfg(test)]
+mod test_utils;
+pub mod tls;
 pub mod utils;
+pub mod verifier;
+pub mod worker;
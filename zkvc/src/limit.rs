@@ -0,0 +1,533 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::response::VerificationResponse;
+
+const PRUNE_THRESHOLD: usize = 10_000;
+const MAX_BAN: Duration = Duration::from_secs(10 * 365 * 24 * 3600);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenBucketConfig {
+    /// Requests that can be made in a burst.
+    pub capacity: u32,
+    pub refill_per_sec: f64,
+}
+
+/// Admission control for `/verify` and `/verify/batch`, applied before any decoding or pairing
+/// work. Every limit is disabled by default.
+///
+/// Peer addresses on the allow list skip rate limits and automatic bans. Client ids can be claimed
+/// by anyone, so `per_client` and `allow_clients` only apply to requests signed with the key
+/// registered for the client they name; unsigned requests are limited by peer address alone.
+/// Clients and addresses on the deny lists are always refused.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Bucket of every client authenticated by its signature.
+    pub per_client: Option<TokenBucketConfig>,
+    pub per_ip: Option<TokenBucketConfig>,
+    /// Ban a client id or peer address after this many invalid or malformed submissions within
+    /// `ban_window_secs`.
+    pub ban_after_invalid: Option<usize>,
+    pub ban_window_secs: u64,
+    pub ban_duration_secs: u64,
+    pub allow_clients: HashSet<String>,
+    pub deny_clients: HashSet<String>,
+    pub allow_ips: HashSet<IpAddr>,
+    pub deny_ips: HashSet<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_client: None,
+            per_ip: None,
+            ban_after_invalid: None,
+            ban_window_secs: 600,
+            ban_duration_secs: 3600,
+            allow_clients: HashSet::new(),
+            deny_clients: HashSet::new(),
+            allow_ips: HashSet::new(),
+            deny_ips: HashSet::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum LimitKey {
+    Client(String),
+    Ip(IpAddr),
+    /// Nonce requests of a peer address, limited separately since every submission needs one.
+    Nonce(IpAddr),
+}
+
+struct Ban {
+    until: Instant,
+    reason: &'static str,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, config: &TokenBucketConfig, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.refill_per_sec).min(config.capacity as f64);
+        self.updated = now;
+    }
+
+    /// Takes a token, or returns how long until one is available.
+    fn take(&mut self, config: &TokenBucketConfig, now: Instant) -> Result<(), Duration> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(
+            Duration::try_from_secs_f64((1.0 - self.tokens) / config.refill_per_sec)
+                .unwrap_or(Duration::MAX),
+        )
+    }
+}
+
+pub(crate) struct Limiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<LimitKey, TokenBucket>>,
+    strikes: Mutex<HashMap<LimitKey, VecDeque<Instant>>>,
+    bans: Mutex<HashMap<LimitKey, Ban>>,
+    revoked: Mutex<HashSet<String>>,
+}
+
+impl Limiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            strikes: Mutex::new(HashMap::new()),
+            bans: Mutex::new(HashMap::new()),
+            revoked: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Decides whether a submission may be processed at all, before it has been authenticated.
+    ///
+    /// The client id is only used to refuse requests naming a denied, revoked or banned client,
+    /// which cannot hurt anyone else. Rate limits are charged to the peer address alone until
+    /// `admit_authenticated` has been called.
+    pub(crate) fn admit(
+        &self,
+        client_id: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), VerificationResponse> {
+        if self.config.deny_clients.contains(client_id)
+            || ip.is_some_and(|ip| self.config.deny_ips.contains(&ip))
+        {
+            return Err(VerificationResponse::Banned {
+                reason: "Client is on the deny list".to_string(),
+                retry_after: None,
+            });
+        }
+        if self.revoked.lock().unwrap().contains(client_id) {
+            return Err(VerificationResponse::Banned {
+                reason: "Client has been revoked".to_string(),
+                retry_after: None,
+            });
+        }
+
+        // Client bans come before the allow lists, since allowed clients are only banned by hand.
+        let now = Instant::now();
+        let ip_allowed = ip.is_some_and(|ip| self.config.allow_ips.contains(&ip));
+        let mut keys = vec![LimitKey::Client(client_id.to_string())];
+        keys.extend(ip.filter(|_| !ip_allowed).map(LimitKey::Ip));
+        self.check_bans(&keys, now)?;
+
+        match ip {
+            Some(ip) if !ip_allowed => self.take_token(LimitKey::Ip(ip), now),
+            _ => Ok(()),
+        }
+    }
+
+    /// Decides whether a peer may be issued a nonce. Nonce requests are not authenticated, so
+    /// they are limited by peer address only, with a bucket of their own using the `per_ip`
+    /// settings.
+    pub(crate) fn admit_nonce(&self, ip: Option<IpAddr>) -> Result<(), VerificationResponse> {
+        let Some(ip) = ip else {
+            return Ok(());
+        };
+        if self.config.deny_ips.contains(&ip) {
+            return Err(VerificationResponse::Banned {
+                reason: "Client is on the deny list".to_string(),
+                retry_after: None,
+            });
+        }
+        if self.config.allow_ips.contains(&ip) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        self.check_bans(&[LimitKey::Ip(ip)], now)?;
+        self.take_token(LimitKey::Nonce(ip), now)
+    }
+
+    fn check_bans(&self, keys: &[LimitKey], now: Instant) -> Result<(), VerificationResponse> {
+        let mut bans = self.bans.lock().unwrap();
+        bans.retain(|_, ban| ban.until > now);
+        let ban = keys
+            .iter()
+            .filter_map(|key| bans.get(key))
+            .max_by_key(|ban| ban.until);
+        match ban {
+            Some(ban) => Err(VerificationResponse::Banned {
+                reason: ban.reason.to_string(),
+                retry_after: Some(whole_secs(ban.until.duration_since(now))),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Charges a submission whose signature showed it comes from `client_id` to the client's own
+    /// bucket, unless the client or its peer address is on an allow list.
+    pub(crate) fn admit_authenticated(
+        &self,
+        client_id: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), VerificationResponse> {
+        if self.is_allowed(client_id, ip) {
+            return Ok(());
+        }
+        self.take_token(LimitKey::Client(client_id.to_string()), Instant::now())
+    }
+
+    fn take_token(&self, key: LimitKey, now: Instant) -> Result<(), VerificationResponse> {
+        let Some(config) = self.bucket_config(&key) else {
+            return Ok(());
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            self.prune(&mut buckets, now);
+        }
+        let bucket = buckets.entry(key).or_insert(TokenBucket {
+            tokens: config.capacity as f64,
+            updated: now,
+        });
+        bucket
+            .take(config, now)
+            .map_err(|wait| VerificationResponse::RateLimited {
+                retry_after: whole_secs(wait),
+            })
+    }
+
+    /// Counts an invalid or malformed submission towards an automatic ban. Returns `true` if it
+    /// caused a new ban.
+    ///
+    /// `client_id` is `None` unless the submission was authenticated as coming from the client it
+    /// names, so that forged requests cannot get someone else banned.
+    pub(crate) fn record_invalid(&self, client_id: Option<&str>, ip: Option<IpAddr>) -> bool {
+        let Some(limit) = self.config.ban_after_invalid else {
+            return false;
+        };
+        if client_id.is_some_and(|client_id| self.config.allow_clients.contains(client_id))
+            || ip.is_some_and(|ip| self.config.allow_ips.contains(&ip))
+        {
+            return false;
+        }
+
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.ban_window_secs);
+        let mut banned = false;
+        let mut strikes = self.strikes.lock().unwrap();
+        let keys = client_id
+            .map(|client_id| LimitKey::Client(client_id.to_string()))
+            .into_iter()
+            .chain(ip.map(LimitKey::Ip));
+        for key in keys {
+            let recent = strikes.entry(key.clone()).or_default();
+            while recent
+                .front()
+                .is_some_and(|at| now.duration_since(*at) > window)
+            {
+                recent.pop_front();
+            }
+            recent.push_back(now);
+            if recent.len() >= limit {
+                strikes.remove(&key);
+                let ban = Ban {
+                    until: now + Duration::from_secs(self.config.ban_duration_secs).min(MAX_BAN),
+                    reason: "Too many invalid submissions",
+                };
+                self.bans.lock().unwrap().insert(key, ban);
+                banned = true;
+            }
+        }
+        if strikes.len() > PRUNE_THRESHOLD {
+            strikes.retain(|_, recent| {
+                recent
+                    .back()
+                    .is_some_and(|at| now.duration_since(*at) <= window)
+            });
+        }
+        banned
+    }
+
+    /// Bans a client id for `duration`, replacing any shorter ban.
+    pub(crate) fn ban(&self, client_id: &str, duration: Duration) {
+        let ban = Ban {
+            until: Instant::now() + duration.min(MAX_BAN),
+            reason: "Client has been banned by an operator",
+        };
+        let mut bans = self.bans.lock().unwrap();
+        let key = LimitKey::Client(client_id.to_string());
+        if bans
+            .get(&key)
+            .is_none_or(|current| current.until < ban.until)
+        {
+            bans.insert(key, ban);
+        }
+    }
+
+    /// Refuses a client id until it is unbanned.
+    pub(crate) fn revoke(&self, client_id: &str) {
+        self.revoked.lock().unwrap().insert(client_id.to_string());
+    }
+
+    /// Lifts a ban or revocation of a client id and forgets its recent invalid submissions.
+    /// Returns `false` if it was neither banned nor revoked.
+    pub(crate) fn unban(&self, client_id: &str) -> bool {
+        let key = LimitKey::Client(client_id.to_string());
+        self.strikes.lock().unwrap().remove(&key);
+        let banned = self.bans.lock().unwrap().remove(&key).is_some();
+        let revoked = self.revoked.lock().unwrap().remove(client_id);
+        banned || revoked
+    }
+
+    /// Remaining ban of a client id, if any.
+    pub(crate) fn banned_for(&self, client_id: &str) -> Option<Duration> {
+        let now = Instant::now();
+        self.bans
+            .lock()
+            .unwrap()
+            .get(&LimitKey::Client(client_id.to_string()))
+            .filter(|ban| ban.until > now)
+            .map(|ban| ban.until.duration_since(now))
+    }
+
+    pub(crate) fn is_revoked(&self, client_id: &str) -> bool {
+        self.revoked.lock().unwrap().contains(client_id)
+    }
+
+    pub(crate) fn revoked(&self) -> Vec<String> {
+        self.revoked.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn is_allowed(&self, client_id: &str, ip: Option<IpAddr>) -> bool {
+        self.config.allow_clients.contains(client_id)
+            || ip.is_some_and(|ip| self.config.allow_ips.contains(&ip))
+    }
+
+    fn bucket_config(&self, key: &LimitKey) -> Option<&TokenBucketConfig> {
+        match key {
+            LimitKey::Client(_) => self.config.per_client.as_ref(),
+            LimitKey::Ip(_) | LimitKey::Nonce(_) => self.config.per_ip.as_ref(),
+        }
+    }
+
+    /// Drops buckets that have refilled completely, since they behave like fresh ones.
+    fn prune(&self, buckets: &mut HashMap<LimitKey, TokenBucket>, now: Instant) {
+        buckets.retain(|key, bucket| match self.bucket_config(key) {
+            Some(config) => {
+                bucket.refill(config, now);
+                bucket.tokens < config.capacity as f64
+            }
+            None => false,
+        });
+    }
+}
+
+fn whole_secs(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil().clamp(1.0, u64::MAX as f64) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER_IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2));
+
+    fn banning_after(strikes: usize) -> RateLimitConfig {
+        RateLimitConfig {
+            ban_after_invalid: Some(strikes),
+            ..RateLimitConfig::default()
+        }
+    }
+
+    #[test]
+    fn everything_is_admitted_by_default() {
+        let limiter = Limiter::new(RateLimitConfig::default());
+        for _ in 0..100 {
+            assert!(limiter.admit("client", Some(IP)).is_ok());
+        }
+        assert!(!limiter.record_invalid(Some("client"), Some(IP)));
+    }
+
+    #[test]
+    fn bucket_limits_bursts_per_client() {
+        let limiter = Limiter::new(RateLimitConfig {
+            per_client: Some(TokenBucketConfig {
+                capacity: 2,
+                refill_per_sec: 0.001,
+            }),
+            ..RateLimitConfig::default()
+        });
+        // Before authentication the claimed id is not charged.
+        for _ in 0..5 {
+            assert!(limiter.admit("client", Some(IP)).is_ok());
+        }
+        assert!(limiter.admit_authenticated("client", Some(IP)).is_ok());
+        assert!(limiter
+            .admit_authenticated("client", Some(OTHER_IP))
+            .is_ok());
+        match limiter.admit_authenticated("client", Some(IP)) {
+            Err(VerificationResponse::RateLimited { retry_after }) => assert!(retry_after >= 1),
+            other => panic!("unexpected admission: {:?}", other),
+        }
+        assert!(limiter.admit_authenticated("other", Some(IP)).is_ok());
+    }
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let mut config = RateLimitConfig {
+            per_ip: Some(TokenBucketConfig {
+                capacity: 1,
+                refill_per_sec: 0.001,
+            }),
+            ..RateLimitConfig::default()
+        };
+        config.per_client = config.per_ip.clone();
+        config.allow_clients.insert("trusted".to_string());
+        config.allow_ips.insert(IP);
+        config.deny_ips.insert(OTHER_IP);
+        let limiter = Limiter::new(config);
+
+        for _ in 0..5 {
+            assert!(limiter.admit("client", Some(IP)).is_ok());
+            assert!(limiter.admit_authenticated("client", Some(IP)).is_ok());
+            assert!(limiter.admit_authenticated("trusted", None).is_ok());
+        }
+        assert!(limiter.admit("client", None).is_ok());
+        assert!(limiter.admit_authenticated("client", None).is_ok());
+        assert!(limiter.admit_authenticated("client", None).is_err());
+        assert!(matches!(
+            limiter.admit("trusted", Some(OTHER_IP)),
+            Err(VerificationResponse::Banned { .. })
+        ));
+    }
+
+    #[test]
+    fn claimed_allowed_client_is_still_limited_by_address() {
+        let mut config = RateLimitConfig {
+            per_ip: Some(TokenBucketConfig {
+                capacity: 1,
+                refill_per_sec: 0.001,
+            }),
+            ..RateLimitConfig::default()
+        };
+        config.allow_clients.insert("trusted".to_string());
+        let limiter = Limiter::new(config);
+
+        assert!(limiter.admit("trusted", Some(IP)).is_ok());
+        assert!(matches!(
+            limiter.admit("trusted", Some(IP)),
+            Err(VerificationResponse::RateLimited { .. })
+        ));
+        assert!(limiter.admit("trusted", Some(OTHER_IP)).is_ok());
+    }
+
+    #[test]
+    fn repeated_invalid_submissions_ban_client_and_address() {
+        let limiter = Limiter::new(banning_after(3));
+        assert!(!limiter.record_invalid(Some("client"), Some(IP)));
+        assert!(!limiter.record_invalid(Some("client"), Some(IP)));
+        assert!(limiter.record_invalid(Some("client"), Some(IP)));
+
+        assert!(limiter.banned_for("client").is_some());
+        match limiter.admit("client", Some(OTHER_IP)) {
+            Err(VerificationResponse::Banned { retry_after, .. }) => {
+                assert_eq!(retry_after, Some(3600))
+            }
+            other => panic!("unexpected admission: {:?}", other),
+        }
+        assert!(limiter.admit("other", Some(IP)).is_err());
+        assert!(limiter.admit("other", Some(OTHER_IP)).is_ok());
+    }
+
+    #[test]
+    fn unattributed_strikes_only_ban_the_address() {
+        let limiter = Limiter::new(banning_after(2));
+        assert!(!limiter.record_invalid(None, Some(IP)));
+        assert!(limiter.record_invalid(None, Some(IP)));
+
+        assert!(limiter.banned_for("client").is_none());
+        assert!(limiter.admit("client", Some(OTHER_IP)).is_ok());
+        assert!(limiter.admit("client", Some(IP)).is_err());
+    }
+
+    #[test]
+    fn huge_ban_duration_is_clamped() {
+        let limiter = Limiter::new(RateLimitConfig {
+            ban_duration_secs: u64::MAX,
+            ..banning_after(1)
+        });
+        assert!(limiter.record_invalid(Some("client"), None));
+        assert!(limiter.banned_for("client").unwrap() <= MAX_BAN);
+
+        limiter.ban("other", Duration::MAX);
+        assert!(limiter.banned_for("other").unwrap() <= MAX_BAN);
+    }
+
+    #[test]
+    fn unban_lifts_bans_and_revocations() {
+        let limiter = Limiter::new(RateLimitConfig::default());
+        limiter.ban("client", Duration::from_secs(60));
+        assert!(limiter.admit("client", None).is_err());
+        assert!(limiter.unban("client"));
+        assert!(limiter.admit("client", None).is_ok());
+
+        limiter.revoke("client");
+        assert!(limiter.is_revoked("client"));
+        assert!(limiter.admit("client", None).is_err());
+        assert!(limiter.unban("client"));
+        assert!(!limiter.unban("client"));
+        assert!(limiter.admit("client", None).is_ok());
+    }
+
+    #[test]
+    fn nonce_requests_have_their_own_bucket() {
+        let limiter = Limiter::new(RateLimitConfig {
+            per_ip: Some(TokenBucketConfig {
+                capacity: 1,
+                refill_per_sec: 0.001,
+            }),
+            ..banning_after(1)
+        });
+        assert!(limiter.admit_nonce(Some(IP)).is_ok());
+        assert!(matches!(
+            limiter.admit_nonce(Some(IP)),
+            Err(VerificationResponse::RateLimited { .. })
+        ));
+        assert!(limiter.admit("client", Some(IP)).is_ok());
+        assert!(limiter.admit_nonce(Some(OTHER_IP)).is_ok());
+
+        limiter.record_invalid(None, Some(OTHER_IP));
+        assert!(matches!(
+            limiter.admit_nonce(Some(OTHER_IP)),
+            Err(VerificationResponse::Banned { .. })
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use std::{collections::HashSet, sync::Mutex};
+
+use crate::response::VerificationResponse;
+
+/// `client_id` label of submissions that were not authenticated by a signature.
+pub(crate) const UNAUTHENTICATED_LABEL: &str = "unauthenticated";
+/// `circuit_id` label of submissions for circuits the server does not know.
+pub(crate) const UNKNOWN_CIRCUIT_LABEL: &str = "unknown";
+
+pub(crate) struct Metrics {
+    registry: Registry,
+    submissions: IntCounterVec,
+    pub(crate) decode_seconds: Histogram,
+    pub(crate) pairing_seconds: HistogramVec,
+    pub(crate) queue_depth: IntGauge,
+    request_bytes: HistogramVec,
+    /// Unsigned client ids that got a label of their own, up to `max_claimed_labels`.
+    claimed_labels: Mutex<HashSet<String>>,
+    max_claimed_labels: usize,
+}
+
+impl Metrics {
+    /// `max_claimed_labels` unsigned client ids get a label of their own; the rest are counted
+    /// under `UNAUTHENTICATED_LABEL`.
+    pub(crate) fn new(max_claimed_labels: usize) -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+
+        let submissions = IntCounterVec::new(
+            Opts::new(
+                "zkvc_submissions_total",
+                "Answered proof submissions by outcome",
+            ),
+            &["outcome", "client_id", "circuit_id"],
+        )?;
+        let decode_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "zkvc_decode_duration_seconds",
+                "Time spent decoding proofs and public inputs",
+            )
+            .buckets(exponential_buckets(0.0001, 2.0, 16)?),
+        )?;
+        let pairing_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "zkvc_pairing_duration_seconds",
+                "Time spent in pairing checks, per single proof or per batch",
+            )
+            .buckets(exponential_buckets(0.001, 2.0, 14)?),
+            &["mode"],
+        )?;
+        let queue_depth = IntGauge::new(
+            "zkvc_verification_queue_depth",
+            "Pairing checks running or waiting for a slot",
+        )?;
+        let request_bytes = HistogramVec::new(
+            HistogramOpts::new("zkvc_request_size_bytes", "Size of verification requests")
+                .buckets(exponential_buckets(256.0, 2.0, 16)?),
+            &["endpoint"],
+        )?;
+
+        registry.register(Box::new(submissions.clone()))?;
+        registry.register(Box::new(decode_seconds.clone()))?;
+        registry.register(Box::new(pairing_seconds.clone()))?;
+        registry.register(Box::new(queue_depth.clone()))?;
+        registry.register(Box::new(request_bytes.clone()))?;
+
+        Ok(Self {
+            registry,
+            submissions,
+            decode_seconds,
+            pairing_seconds,
+            queue_depth,
+            request_bytes,
+            claimed_labels: Mutex::new(HashSet::new()),
+            max_claimed_labels,
+        })
+    }
+
+    /// The `client_id` label of a submission naming `client_id`.
+    pub(crate) fn client_label<'a>(&self, client_id: &'a str, authenticated: bool) -> &'a str {
+        if authenticated {
+            return client_id;
+        }
+        let mut claimed = self.claimed_labels.lock().unwrap();
+        if claimed.contains(client_id) {
+            return client_id;
+        }
+        if claimed.len() < self.max_claimed_labels {
+            claimed.insert(client_id.to_string());
+            return client_id;
+        }
+        UNAUTHENTICATED_LABEL
+    }
+
+    pub(crate) fn observe_response(
+        &self,
+        client_id: &str,
+        circuit_id: &str,
+        response: &VerificationResponse,
+    ) {
+        self.submissions
+            .with_label_values(&[outcome(response), client_id, circuit_id])
+            .inc();
+    }
+
+    pub(crate) fn observe_request_size(&self, endpoint: &str, bytes: usize) {
+        self.request_bytes
+            .with_label_values(&[endpoint])
+            .observe(bytes as f64);
+    }
+
+    pub(crate) fn encode(&self) -> Result<String, anyhow::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+fn outcome(response: &VerificationResponse) -> &'static str {
+    match response {
+        VerificationResponse::Valid { .. } => "valid",
+        VerificationResponse::Invalid { .. } => "invalid",
+        VerificationResponse::Malformed { .. } => "malformed",
+        VerificationResponse::Unauthorized { .. } => "unauthorized",
+        VerificationResponse::Rejected { .. } => "rejected",
+        VerificationResponse::Duplicate {} => "duplicate",
+        VerificationResponse::StaleNonce {} => "stale_nonce",
+        VerificationResponse::Retry { .. } => "retry",
+        VerificationResponse::RateLimited { .. } => "rate_limited",
+        VerificationResponse::Banned { .. } => "banned",
+        VerificationResponse::Error { .. } => "error",
+    }
+}
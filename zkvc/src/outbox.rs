@@ -0,0 +1,349 @@
+use crate::circuit::ProofRequest;
+use log::debug;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Directory where `ClientApp` keeps proofs until the server has answered them, so they survive
+/// outages and restarts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutboxConfig {
+    pub dir: PathBuf,
+    /// Wait before the first resubmission, doubled for every further attempt.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// Submissions per proof and run. A proof that still has no answer stays in the outbox for
+    /// `ClientApp::resume_outbox`.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_secs() -> u64 {
+    60
+}
+
+fn default_max_attempts() -> u32 {
+    8
+}
+
+impl OutboxConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_secs: default_max_backoff_secs(),
+            max_attempts: default_max_attempts(),
+        }
+    }
+}
+
+pub(crate) struct Outbox {
+    config: OutboxConfig,
+}
+
+impl Outbox {
+    pub(crate) fn open(config: &OutboxConfig) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(&config.dir)?;
+        Ok(Self {
+            config: config.clone(),
+        })
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    pub(crate) fn max_attempts(&self) -> u32 {
+        self.config.max_attempts.max(1)
+    }
+
+    /// Writes `request` to a new entry. Entry names start with the creation time, so sorting them
+    /// yields the oldest proof first.
+    pub(crate) fn store(&self, request: &ProofRequest) -> Result<PathBuf, anyhow::Error> {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let digest = Sha256::digest(request.proof().as_bytes());
+        let path =
+            self.config
+                .dir
+                .join(format!("{:013}-{}.json", millis, hex::encode(&digest[..8])));
+        self.update(&path, request)?;
+        Ok(path)
+    }
+
+    /// Replaces the entry at `path` without leaving a half-written file behind. The file and the
+    /// directory are synced before this returns, so the entry survives a power loss.
+    pub(crate) fn update(&self, path: &Path, request: &ProofRequest) -> Result<(), anyhow::Error> {
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(request)?)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp, path)?;
+        self.sync_dir()?;
+        debug!("Stored proof in outbox entry {}", path.display());
+        Ok(())
+    }
+
+    pub(crate) fn remove(&self, path: &Path) -> Result<(), anyhow::Error> {
+        std::fs::remove_file(path)?;
+        self.sync_dir()?;
+        debug!("Removed outbox entry {}", path.display());
+        Ok(())
+    }
+
+    /// Makes renames and removals in the outbox directory durable.
+    #[cfg(unix)]
+    fn sync_dir(&self) -> Result<(), anyhow::Error> {
+        File::open(&self.config.dir)?.sync_all()?;
+        Ok(())
+    }
+
+    /// Directories cannot be opened for syncing here; renames are as durable as the platform
+    /// makes them.
+    #[cfg(not(unix))]
+    fn sync_dir(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    /// Entries still waiting for an answer, oldest first.
+    pub(crate) fn pending(&self) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.config.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                entries.push(path);
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Exponential backoff with jitter before attempt `attempt + 1`, so clients knocked out by the
+    /// same outage do not all come back at once.
+    pub(crate) fn retry_delay(&self, attempt: u32) -> Duration {
+        let base = Duration::from_millis(self.config.initial_backoff_ms)
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(Duration::from_secs(self.config.max_backoff_secs));
+        base.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::ClientApp,
+        response::VerificationResponse,
+        server::ServerApp,
+        test_utils::{client_config, request, server_config, ProductCircuit},
+    };
+    use std::net::{SocketAddr, TcpListener};
+
+    fn outbox_config(dir: &Path) -> OutboxConfig {
+        OutboxConfig {
+            initial_backoff_ms: 10,
+            max_backoff_secs: 1,
+            max_attempts: 2,
+            ..OutboxConfig::new(dir.join("outbox"))
+        }
+    }
+
+    /// An address nothing listens on.
+    fn closed_address() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    fn client(dir: &Path, server: SocketAddr) -> ClientApp {
+        let mut config = client_config(dir, server, "client");
+        config.outbox = Some(outbox_config(dir));
+        ClientApp::new(config).unwrap()
+    }
+
+    #[test]
+    fn entries_persist_oldest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = outbox_config(dir.path());
+        let first = request("client", 3, 5);
+        let second = request("client", 2, 7);
+        {
+            let outbox = Outbox::open(&config).unwrap();
+            outbox.store(&first).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+            outbox.store(&second).unwrap();
+        }
+
+        let outbox = Outbox::open(&config).unwrap();
+        let pending = outbox.pending().unwrap();
+        let stored: Vec<_> = pending
+            .iter()
+            .map(|path| ProofRequest::load(path).unwrap())
+            .collect();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].proof(), first.proof());
+        assert_eq!(stored[1].proof(), second.proof());
+
+        outbox
+            .update(&pending[0], &first.clone().with_nonce("nonce"))
+            .unwrap();
+        assert_eq!(
+            ProofRequest::load(&pending[0]).unwrap().nonce(),
+            Some("nonce")
+        );
+        outbox.remove(&pending[1]).unwrap();
+        assert_eq!(outbox.pending().unwrap(), &pending[..1]);
+        assert_eq!(std::fs::read_dir(outbox.dir()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn retry_delay_doubles_with_jitter_up_to_the_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = Outbox::open(&OutboxConfig {
+            initial_backoff_ms: 100,
+            max_backoff_secs: 1,
+            ..OutboxConfig::new(dir.path())
+        })
+        .unwrap();
+        for (attempt, base) in [(1, 100), (2, 200), (3, 400), (10, 1000)] {
+            let delay = outbox.retry_delay(attempt).as_millis();
+            assert!(
+                (base / 2..=base).contains(&delay),
+                "attempt {}: {:?}",
+                attempt,
+                delay
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unanswered_proofs_are_resumed_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let offline = client(dir.path(), closed_address());
+        let proof = offline
+            .prove(Box::new(ProductCircuit { a: 3, b: 5 }))
+            .await
+            .unwrap();
+        assert!(offline.submit(proof).await.is_err());
+        drop(offline);
+
+        let outbox = Outbox::open(&outbox_config(dir.path())).unwrap();
+        assert_eq!(outbox.pending().unwrap().len(), 1);
+
+        let handle = ServerApp::new(server_config(dir.path()))
+            .unwrap()
+            .start()
+            .unwrap();
+        let online = client(dir.path(), handle.local_addr());
+        let responses = online.resume_outbox().await.unwrap();
+        assert_eq!(responses.len(), 1);
+        assert!(matches!(responses[0], VerificationResponse::Valid { .. }));
+        assert!(outbox.pending().unwrap().is_empty());
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resumed_proofs_get_a_fresh_nonce() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = Outbox::open(&outbox_config(dir.path())).unwrap();
+        outbox
+            .store(&request("client", 3, 5).with_nonce("expired"))
+            .unwrap();
+
+        let handle = ServerApp::new(server_config(dir.path()))
+            .unwrap()
+            .start()
+            .unwrap();
+        let responses = client(dir.path(), handle.local_addr())
+            .resume_outbox()
+            .await
+            .unwrap();
+        assert!(matches!(
+            responses[..],
+            [VerificationResponse::Valid { .. }]
+        ));
+        assert!(outbox.pending().unwrap().is_empty());
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failing_entries_do_not_block_later_ones() {
+        use actix_web::{web, App, HttpResponse, HttpServer};
+
+        // Answers proofs from "broken" with a body that is not a verification response.
+        let server = HttpServer::new(|| {
+            App::new().route(
+                "/verify",
+                web::post().to(|request: web::Json<ProofRequest>| async move {
+                    if request.client_id == "broken" {
+                        HttpResponse::Ok().body("not json")
+                    } else {
+                        HttpResponse::Ok().json(VerificationResponse::Valid { result: None })
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let server_handle = server.handle();
+        tokio::spawn(server);
+
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = Outbox::open(&outbox_config(dir.path())).unwrap();
+        let broken = outbox
+            .store(&request("broken", 3, 5).with_nonce("nonce"))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        outbox
+            .store(&request("client", 2, 7).with_nonce("nonce"))
+            .unwrap();
+
+        let responses = client(dir.path(), address).resume_outbox().await.unwrap();
+        assert!(matches!(
+            responses[..],
+            [VerificationResponse::Valid { .. }]
+        ));
+        assert_eq!(outbox.pending().unwrap(), [broken]);
+
+        server_handle.stop(true).await;
+    }
+
+    #[tokio::test]
+    async fn resume_stops_while_the_server_is_unreachable() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = Outbox::open(&outbox_config(dir.path())).unwrap();
+        for (a, b) in [(3, 5), (2, 7)] {
+            outbox
+                .store(&request("client", a, b).with_nonce("nonce"))
+                .unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        let client = client(dir.path(), closed_address());
+        assert!(client.resume_outbox().await.is_err());
+        assert_eq!(outbox.pending().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,325 @@
+use ark_bls12_381::Fr;
+use std::ops::Range;
+
+use crate::{challenge::DynChallengeProvider, response::PolicyViolation, utils::field_to_string};
+
+type ExpectedInputsFn = Box<dyn Fn() -> Vec<Fr> + Send + Sync>;
+
+enum InputRule {
+    Count(usize),
+    Equal {
+        range: Range<usize>,
+        expected: Vec<Fr>,
+    },
+    EqualTo {
+        range: Range<usize>,
+        expected: ExpectedInputsFn,
+    },
+    OneOf {
+        index: usize,
+        allowed: Vec<Fr>,
+    },
+    Challenge {
+        range: Range<usize>,
+    },
+}
+
+/// Rules on the public inputs of a proof, checked right after the pairing check and before any
+/// challenge validation or handler runs. The circuit's challenge cannot rotate while they are
+/// checked.
+#[derive(Default)]
+pub struct InputPolicy {
+    rules: Vec<InputRule>,
+}
+
+impl InputPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn require_count(mut self, count: usize) -> Self {
+        self.rules.push(InputRule::Count(count));
+        self
+    }
+
+    pub fn require_equal(mut self, range: Range<usize>, expected: Vec<Fr>) -> Self {
+        self.rules.push(InputRule::Equal { range, expected });
+        self
+    }
+
+    /// Like `require_equal`, but the expected values are computed for every proof. Use
+    /// `require_challenge` to compare against the current challenge.
+    pub fn require_equal_to<F>(mut self, range: Range<usize>, expected: F) -> Self
+    where
+        F: Fn() -> Vec<Fr> + Send + Sync + 'static,
+    {
+        self.rules.push(InputRule::EqualTo {
+            range,
+            expected: Box::new(expected),
+        });
+        self
+    }
+
+    pub fn require_one_of(mut self, index: usize, allowed: Vec<Fr>) -> Self {
+        self.rules.push(InputRule::OneOf { index, allowed });
+        self
+    }
+
+    /// Requires the inputs in `range` to equal `ChallengeProvider::expected_inputs` of the circuit's
+    /// challenge provider.
+    pub fn require_challenge(mut self, range: Range<usize>) -> Self {
+        self.rules.push(InputRule::Challenge { range });
+        self
+    }
+
+    /// Checks the rules without a challenge, so `require_challenge` rules fail.
+    pub fn check(&self, public_inputs: &[Fr]) -> Result<(), PolicyViolation> {
+        self.check_with_challenge(public_inputs, None)
+    }
+
+    pub(crate) fn check_with_challenge(
+        &self,
+        public_inputs: &[Fr],
+        challenge: Option<&dyn DynChallengeProvider>,
+    ) -> Result<(), PolicyViolation> {
+        for rule in &self.rules {
+            match rule {
+                InputRule::Count(count) => {
+                    if public_inputs.len() != *count {
+                        return Err(PolicyViolation {
+                            rule: "count".to_string(),
+                            index: None,
+                            message: format!(
+                                "Expected {} public inputs, got {}",
+                                count,
+                                public_inputs.len()
+                            ),
+                        });
+                    }
+                }
+                InputRule::Equal { range, expected } => {
+                    check_equal(public_inputs, range, expected, "equal")?;
+                }
+                InputRule::EqualTo { range, expected } => {
+                    check_equal(public_inputs, range, &expected(), "equal")?;
+                }
+                InputRule::Challenge { range } => {
+                    let expected = challenge
+                        .and_then(|challenge| challenge.expected_inputs())
+                        .ok_or_else(|| PolicyViolation {
+                            rule: "challenge".to_string(),
+                            index: None,
+                            message: "Circuit has no challenge to compare the public inputs with"
+                                .to_string(),
+                        })?;
+                    check_equal(public_inputs, range, &expected, "challenge")?;
+                }
+                InputRule::OneOf { index, allowed } => {
+                    let input = input_at(public_inputs, *index, "one_of")?;
+                    if !allowed.contains(input) {
+                        return Err(PolicyViolation {
+                            rule: "one_of".to_string(),
+                            index: Some(*index),
+                            message: format!(
+                                "Public input {} has value {}, which is not in the allowed set",
+                                index,
+                                field_to_string(*input)
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn input_at<'a>(
+    public_inputs: &'a [Fr],
+    index: usize,
+    rule: &str,
+) -> Result<&'a Fr, PolicyViolation> {
+    public_inputs.get(index).ok_or_else(|| PolicyViolation {
+        rule: rule.to_string(),
+        index: Some(index),
+        message: format!(
+            "Public input {} is missing, only {} inputs were provided",
+            index,
+            public_inputs.len()
+        ),
+    })
+}
+
+fn check_equal(
+    public_inputs: &[Fr],
+    range: &Range<usize>,
+    expected: &[Fr],
+    rule: &str,
+) -> Result<(), PolicyViolation> {
+    if range.len() != expected.len() {
+        return Err(PolicyViolation {
+            rule: rule.to_string(),
+            index: Some(range.start),
+            message: format!(
+                "Rule covers {} public inputs but expects {} values",
+                range.len(),
+                expected.len()
+            ),
+        });
+    }
+
+    for (index, expected) in range.clone().zip(expected) {
+        let input = input_at(public_inputs, index, rule)?;
+        if input != expected {
+            return Err(PolicyViolation {
+                rule: rule.to_string(),
+                index: Some(index),
+                message: format!(
+                    "Public input {} is {}, expected {}",
+                    index,
+                    field_to_string(*input),
+                    field_to_string(*expected)
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::challenge::ChallengeProvider;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    fn inputs(values: &[u64]) -> Vec<Fr> {
+        values.iter().map(|&value| Fr::from(value)).collect()
+    }
+
+    struct FixedChallenge(Option<u64>);
+
+    impl ChallengeProvider for FixedChallenge {
+        type Challenge = Option<u64>;
+
+        fn issue(&self) -> Option<u64> {
+            self.0
+        }
+
+        fn rotate(&self) {}
+
+        fn validate(&self, _public_inputs: &[Fr]) -> bool {
+            true
+        }
+
+        fn expected_inputs(&self) -> Option<Vec<Fr>> {
+            self.0.map(|value| vec![Fr::from(value)])
+        }
+    }
+
+    #[test]
+    fn empty_policy_accepts_anything() {
+        assert!(InputPolicy::new().check(&inputs(&[1, 2, 3])).is_ok());
+    }
+
+    #[test]
+    fn count_rule() {
+        let policy = InputPolicy::new().require_count(2);
+        assert!(policy.check(&inputs(&[1, 2])).is_ok());
+
+        let violation = policy.check(&inputs(&[1, 2, 3])).unwrap_err();
+        assert_eq!(violation.rule, "count");
+        assert_eq!(violation.index, None);
+    }
+
+    #[test]
+    fn equal_rule_names_the_first_mismatch() {
+        let policy = InputPolicy::new().require_equal(1..3, inputs(&[5, 6]));
+        assert!(policy.check(&inputs(&[0, 5, 6])).is_ok());
+
+        let violation = policy.check(&inputs(&[0, 5, 7])).unwrap_err();
+        assert_eq!(violation.rule, "equal");
+        assert_eq!(violation.index, Some(2));
+    }
+
+    #[test]
+    fn equal_rule_reports_missing_inputs() {
+        let policy = InputPolicy::new().require_equal(1..3, inputs(&[5, 6]));
+        let violation = policy.check(&inputs(&[0, 5])).unwrap_err();
+        assert_eq!(violation.index, Some(2));
+        assert!(violation.message.contains("missing"));
+    }
+
+    #[test]
+    fn equal_rule_with_wrong_length_never_matches() {
+        let policy = InputPolicy::new().require_equal(0..2, inputs(&[5]));
+        let violation = policy.check(&inputs(&[5, 5])).unwrap_err();
+        assert_eq!(violation.index, Some(0));
+    }
+
+    #[test]
+    fn equal_to_rule_is_evaluated_for_every_check() {
+        let expected = Arc::new(AtomicU64::new(1));
+        let current = Arc::clone(&expected);
+        let policy = InputPolicy::new()
+            .require_equal_to(0..1, move || vec![Fr::from(current.load(Ordering::SeqCst))]);
+
+        assert!(policy.check(&inputs(&[1])).is_ok());
+        expected.store(2, Ordering::SeqCst);
+        assert!(policy.check(&inputs(&[1])).is_err());
+        assert!(policy.check(&inputs(&[2])).is_ok());
+    }
+
+    #[test]
+    fn one_of_rule() {
+        let policy = InputPolicy::new().require_one_of(1, inputs(&[10, 20]));
+        assert!(policy.check(&inputs(&[0, 20])).is_ok());
+
+        let violation = policy.check(&inputs(&[0, 30])).unwrap_err();
+        assert_eq!(violation.rule, "one_of");
+        assert_eq!(violation.index, Some(1));
+
+        let violation = policy.check(&inputs(&[0])).unwrap_err();
+        assert!(violation.message.contains("missing"));
+    }
+
+    #[test]
+    fn challenge_rule_compares_with_the_provider() {
+        let policy = InputPolicy::new().require_challenge(1..2);
+        let challenge = FixedChallenge(Some(15));
+
+        assert!(policy
+            .check_with_challenge(&inputs(&[0, 15]), Some(&challenge))
+            .is_ok());
+
+        let violation = policy
+            .check_with_challenge(&inputs(&[0, 16]), Some(&challenge))
+            .unwrap_err();
+        assert_eq!(violation.rule, "challenge");
+        assert_eq!(violation.index, Some(1));
+    }
+
+    #[test]
+    fn challenge_rule_fails_without_expected_inputs() {
+        let policy = InputPolicy::new().require_challenge(0..1);
+
+        let violation = policy.check(&inputs(&[15])).unwrap_err();
+        assert_eq!(violation.rule, "challenge");
+
+        let violation = policy
+            .check_with_challenge(&inputs(&[15]), Some(&FixedChallenge(None)))
+            .unwrap_err();
+        assert_eq!(violation.rule, "challenge");
+    }
+
+    #[test]
+    fn rules_are_checked_in_order() {
+        let policy = InputPolicy::new()
+            .require_count(2)
+            .require_one_of(0, inputs(&[1]));
+        assert_eq!(policy.check(&inputs(&[2])).unwrap_err().rule, "count");
+        assert_eq!(policy.check(&inputs(&[2, 0])).unwrap_err().rule, "one_of");
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::sync::Semaphore;
+
+#[derive(Debug)]
+pub(crate) enum PoolError {
+    Full,
+    Failed(tokio::task::JoinError),
+}
+
+/// Runs pairing checks on the blocking thread pool, at most `concurrency` at a time and with at
+/// most `queue_depth` further checks waiting for a slot.
+pub(crate) struct VerificationPool {
+    permits: Arc<Semaphore>,
+    pending: Arc<AtomicUsize>,
+    concurrency: usize,
+    capacity: usize,
+}
+
+impl VerificationPool {
+    pub(crate) fn new(concurrency: usize, queue_depth: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency)),
+            pending: Arc::new(AtomicUsize::new(0)),
+            concurrency,
+            capacity: concurrency + queue_depth,
+        }
+    }
+
+    pub(crate) async fn run<T, F>(&self, f: F) -> Result<T, PoolError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if self.pending.fetch_add(1, Ordering::SeqCst) >= self.capacity {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            return Err(PoolError::Full);
+        }
+        let slot = PendingSlot(Arc::clone(&self.pending));
+
+        let permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .expect("verification pool semaphore is never closed");
+        // The slot and permit go with the check, so a caller that stops waiting does not free them
+        // while the check is still running.
+        tokio::task::spawn_blocking(move || {
+            // Dropped in reverse order: the slot is given back before `drain` can take the permit.
+            let _permit = permit;
+            let _slot = slot;
+            f()
+        })
+        .await
+        .map_err(PoolError::Failed)
+    }
+
+    /// Checks running or waiting for a slot.
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Waits until every running check has finished.
+    pub(crate) async fn drain(&self) {
+        let _permits = self
+            .permits
+            .acquire_many(self.concurrency as u32)
+            .await
+            .expect("verification pool semaphore is never closed");
+    }
+}
+
+struct PendingSlot(Arc<AtomicUsize>);
+
+impl Drop for PendingSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[tokio::test]
+    async fn refuses_work_beyond_capacity() {
+        let pool = Arc::new(VerificationPool::new(1, 1));
+        let (release, wait) = mpsc::channel::<()>();
+        let wait = Arc::new(std::sync::Mutex::new(wait));
+
+        let mut running = Vec::new();
+        for _ in 0..2 {
+            let pool = Arc::clone(&pool);
+            let wait = Arc::clone(&wait);
+            running.push(tokio::spawn(async move {
+                pool.run(move || wait.lock().unwrap().recv().unwrap()).await
+            }));
+        }
+        while pool.pending() < 2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert!(matches!(pool.run(|| ()).await, Err(PoolError::Full)));
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        for task in running {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(pool.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_caller_keeps_the_check_counted() {
+        let pool = Arc::new(VerificationPool::new(1, 0));
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release, wait) = mpsc::channel::<()>();
+
+        let caller = {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move {
+                pool.run(move || {
+                    started_tx.send(()).unwrap();
+                    wait.recv().unwrap();
+                })
+                .await
+            })
+        };
+        started_rx.await.unwrap();
+        caller.abort();
+        assert!(caller.await.unwrap_err().is_cancelled());
+
+        assert_eq!(pool.pending(), 1);
+        assert!(matches!(pool.run(|| ()).await, Err(PoolError::Full)));
+        let drained = tokio::time::timeout(Duration::from_millis(50), pool.drain()).await;
+        assert!(drained.is_err(), "drain returned while a check was running");
+
+        release.send(()).unwrap();
+        pool.drain().await;
+        assert_eq!(pool.pending(), 0);
+    }
+}
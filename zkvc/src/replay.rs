@@ -0,0 +1,396 @@
+use ark_bls12_381::Fr;
+use ark_serialize::CanonicalSerialize;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+pub type SubmissionDigest = [u8; 32];
+
+/// Canonical digest of a submission: the circuit id and the public inputs.
+///
+/// Re-randomized proofs of the same statement share a digest, so duplicates are detected without
+/// comparing proof bytes. The nonce is left out because proofs do not commit to it: an accepted
+/// proof sent again under a fresh nonce, or under another client id, is still a duplicate.
+pub fn submission_digest(circuit_id: &str, public_inputs: &[Fr]) -> SubmissionDigest {
+    let mut hasher = Sha256::new();
+    hasher.update(b"zkvc-submission-v2");
+    hasher.update((circuit_id.len() as u64).to_le_bytes());
+    hasher.update(circuit_id.as_bytes());
+    hasher.update((public_inputs.len() as u64).to_le_bytes());
+    for input in public_inputs {
+        let mut bytes = Vec::with_capacity(input.serialized_size());
+        input
+            .serialize(&mut bytes)
+            .expect("serializing a field element into a Vec cannot fail");
+        hasher.update(&bytes);
+    }
+    hasher.finalize().into()
+}
+
+/// Record of accepted submissions used to detect duplicates within the retention window.
+pub trait SeenSet: Send + Sync + 'static {
+    /// Records `digest` until `expires_at`. Returns `false` if it is already recorded.
+    fn insert(&self, digest: SubmissionDigest, expires_at: SystemTime) -> bool;
+
+    fn contains(&self, digest: &SubmissionDigest) -> bool;
+
+    fn remove(&self, digest: &SubmissionDigest);
+}
+
+/// `SeenSet` kept in memory. Expired digests are dropped in expiry order as new ones come in.
+#[derive(Default)]
+pub struct InMemorySeenSet {
+    state: Mutex<SeenEntries>,
+}
+
+#[derive(Default)]
+struct SeenEntries {
+    entries: HashMap<SubmissionDigest, SystemTime>,
+    /// Every recorded digest by expiry, including removed ones, which are skipped when they come up.
+    expiries: BinaryHeap<Reverse<(SystemTime, SubmissionDigest)>>,
+}
+
+impl InMemorySeenSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SeenEntries {
+    fn expire(&mut self, now: SystemTime) {
+        while let Some(Reverse((expires_at, digest))) = self.expiries.peek().copied() {
+            if expires_at > now {
+                break;
+            }
+            self.expiries.pop();
+            if self.entries.get(&digest) == Some(&expires_at) {
+                self.entries.remove(&digest);
+            }
+        }
+    }
+}
+
+impl SeenSet for InMemorySeenSet {
+    fn insert(&self, digest: SubmissionDigest, expires_at: SystemTime) -> bool {
+        let now = SystemTime::now();
+        let mut state = self.state.lock().unwrap();
+        state.expire(now);
+        if state
+            .entries
+            .get(&digest)
+            .is_some_and(|expires_at| *expires_at > now)
+        {
+            return false;
+        }
+        state.entries.insert(digest, expires_at);
+        state.expiries.push(Reverse((expires_at, digest)));
+        true
+    }
+
+    fn contains(&self, digest: &SubmissionDigest) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .entries
+            .get(digest)
+            .is_some_and(|expires_at| *expires_at > SystemTime::now())
+    }
+
+    fn remove(&self, digest: &SubmissionDigest) {
+        self.state.lock().unwrap().entries.remove(digest);
+    }
+}
+
+/// Nonces handed out at `/nonce`, each valid for `ttl` or until it is taken. At most `capacity`
+/// are outstanding at a time, and at most `per_peer` for any one peer address, so a flood of nonce
+/// requests can neither grow memory without bound nor use up every slot.
+pub(crate) struct NonceIssuer {
+    state: Mutex<IssuedNonces>,
+    ttl: Duration,
+    capacity: usize,
+    per_peer: usize,
+}
+
+/// Expiry and requesting peer of an outstanding nonce, returned by `NonceIssuer::take`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct IssuedNonce {
+    expires_at: SystemTime,
+    peer: Option<IpAddr>,
+}
+
+#[derive(Default)]
+struct IssuedNonces {
+    issued: HashMap<String, IssuedNonce>,
+    /// Outstanding nonces per peer address.
+    peers: HashMap<IpAddr, usize>,
+    /// Every issued nonce by expiry. Taken ones are skipped when they come up, and dropped early
+    /// once they make up most of the heap.
+    expiries: BinaryHeap<Reverse<(SystemTime, String)>>,
+}
+
+impl IssuedNonces {
+    fn expire(&mut self, now: SystemTime) {
+        while let Some(Reverse((expires_at, _))) = self.expiries.peek() {
+            if *expires_at > now {
+                break;
+            }
+            let Reverse((expires_at, nonce)) = self.expiries.pop().unwrap();
+            if self
+                .issued
+                .get(&nonce)
+                .is_some_and(|issued| issued.expires_at == expires_at)
+            {
+                self.remove(&nonce);
+            }
+        }
+        if self.expiries.len() > 2 * self.issued.len() + 64 {
+            let issued = &self.issued;
+            self.expiries.retain(|Reverse((expires_at, nonce))| {
+                issued
+                    .get(nonce)
+                    .is_some_and(|issued| issued.expires_at == *expires_at)
+            });
+        }
+    }
+
+    fn insert(&mut self, nonce: String, issued: IssuedNonce) {
+        if let Some(peer) = issued.peer {
+            *self.peers.entry(peer).or_default() += 1;
+        }
+        self.expiries
+            .push(Reverse((issued.expires_at, nonce.clone())));
+        self.issued.insert(nonce, issued);
+    }
+
+    fn remove(&mut self, nonce: &str) -> Option<IssuedNonce> {
+        let issued = self.issued.remove(nonce)?;
+        if let Some(peer) = issued.peer {
+            if let Some(count) = self.peers.get_mut(&peer) {
+                *count -= 1;
+                if *count == 0 {
+                    self.peers.remove(&peer);
+                }
+            }
+        }
+        Some(issued)
+    }
+}
+
+impl NonceIssuer {
+    pub(crate) fn new(ttl: Duration, capacity: usize, per_peer: usize) -> Self {
+        Self {
+            state: Mutex::new(IssuedNonces::default()),
+            ttl,
+            capacity,
+            per_peer,
+        }
+    }
+
+    /// Returns `None` while `capacity` nonces, or `per_peer` nonces for `peer`, are outstanding.
+    pub(crate) fn issue(&self, peer: Option<IpAddr>) -> Option<String> {
+        let now = SystemTime::now();
+        let mut state = self.state.lock().unwrap();
+        state.expire(now);
+        if state.issued.len() >= self.capacity {
+            return None;
+        }
+        if peer.is_some_and(|peer| state.peers.get(&peer).is_some_and(|n| *n >= self.per_peer)) {
+            return None;
+        }
+
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let nonce = hex::encode(bytes);
+        let issued = IssuedNonce {
+            expires_at: now + self.ttl,
+            peer,
+        };
+        state.insert(nonce.clone(), issued);
+        Some(nonce)
+    }
+
+    pub(crate) fn is_valid(&self, nonce: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .issued
+            .get(nonce)
+            .is_some_and(|issued| issued.expires_at > SystemTime::now())
+    }
+
+    /// Removes a valid nonce, freeing its slot, so of several requests sharing a nonce only one
+    /// gets it. `restore` puts it back if that request is not accepted after all.
+    pub(crate) fn take(&self, nonce: &str) -> Option<IssuedNonce> {
+        self.state
+            .lock()
+            .unwrap()
+            .remove(nonce)
+            .filter(|issued| issued.expires_at > SystemTime::now())
+    }
+
+    /// Puts back a nonce returned by `take`, unless it has expired in the meantime.
+    pub(crate) fn restore(&self, nonce: &str, issued: IssuedNonce) {
+        if issued.expires_at > SystemTime::now() {
+            self.state.lock().unwrap().insert(nonce.to_string(), issued);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    fn inputs(values: &[u64]) -> Vec<Fr> {
+        values.iter().map(|&value| Fr::from(value)).collect()
+    }
+
+    #[test]
+    fn digest_depends_on_circuit_and_inputs() {
+        let digest = submission_digest("default", &inputs(&[1, 2]));
+        assert_eq!(digest, submission_digest("default", &inputs(&[1, 2])));
+        assert_ne!(digest, submission_digest("other", &inputs(&[1, 2])));
+        assert_ne!(digest, submission_digest("default", &inputs(&[2, 1])));
+        assert_ne!(digest, submission_digest("default", &inputs(&[1, 2, 0])));
+    }
+
+    #[test]
+    fn digest_separates_circuit_id_from_inputs() {
+        assert_ne!(
+            submission_digest("a", &inputs(&[])),
+            submission_digest("", &inputs(&[]))
+        );
+    }
+
+    #[test]
+    fn seen_set_records_each_digest_once() {
+        let seen = InMemorySeenSet::new();
+        let digest = submission_digest("default", &inputs(&[1]));
+        let expires_at = SystemTime::now() + Duration::from_secs(60);
+
+        assert!(!seen.contains(&digest));
+        assert!(seen.insert(digest, expires_at));
+        assert!(seen.contains(&digest));
+        assert!(!seen.insert(digest, expires_at));
+
+        seen.remove(&digest);
+        assert!(!seen.contains(&digest));
+        assert!(seen.insert(digest, expires_at));
+    }
+
+    #[test]
+    fn seen_set_forgets_expired_digests() {
+        let seen = InMemorySeenSet::new();
+        let digest = submission_digest("default", &inputs(&[1]));
+        assert!(seen.insert(digest, SystemTime::now() - Duration::from_secs(1)));
+        assert!(!seen.contains(&digest));
+        assert!(seen.insert(digest, SystemTime::now() + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn nonce_can_be_taken_once() {
+        let nonces = NonceIssuer::new(Duration::from_secs(60), 16, 16);
+        let nonce = nonces.issue(None).unwrap();
+        assert_ne!(nonce, nonces.issue(None).unwrap());
+
+        assert!(nonces.is_valid(&nonce));
+        let expires_at = nonces.take(&nonce).unwrap();
+        assert!(!nonces.is_valid(&nonce));
+        assert!(nonces.take(&nonce).is_none());
+
+        nonces.restore(&nonce, expires_at);
+        assert!(nonces.take(&nonce).is_some());
+    }
+
+    #[test]
+    fn unknown_and_expired_nonces_are_refused() {
+        let nonces = NonceIssuer::new(Duration::ZERO, 16, 16);
+        let nonce = nonces.issue(None).unwrap();
+        assert!(!nonces.is_valid(&nonce));
+        assert!(nonces.take(&nonce).is_none());
+        assert!(nonces.take("not issued").is_none());
+    }
+
+    #[test]
+    fn concurrent_takes_share_one_nonce() {
+        let nonces = Arc::new(NonceIssuer::new(Duration::from_secs(60), 16, 16));
+        let nonce = nonces.issue(None).unwrap();
+        let takers: Vec<_> = (0..8)
+            .map(|_| {
+                let nonces = Arc::clone(&nonces);
+                let nonce = nonce.clone();
+                thread::spawn(move || nonces.take(&nonce).is_some())
+            })
+            .collect();
+        let taken = takers
+            .into_iter()
+            .map(|taker| taker.join().unwrap())
+            .filter(|taken| *taken)
+            .count();
+        assert_eq!(taken, 1);
+    }
+
+    #[test]
+    fn outstanding_nonces_are_capped_until_taken_or_expired() {
+        let nonces = NonceIssuer::new(Duration::from_millis(50), 2, 2);
+        let first = nonces.issue(None).unwrap();
+        nonces.issue(None).unwrap();
+        assert!(nonces.issue(None).is_none());
+
+        // Taking a nonce frees its slot, and restoring it takes the slot again.
+        let issued = nonces.take(&first).unwrap();
+        let third = nonces.issue(None).unwrap();
+        nonces.restore(&first, issued);
+        assert!(nonces.is_valid(&first));
+        nonces.take(&third).unwrap();
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(nonces.issue(None).is_some());
+        assert_eq!(nonces.state.lock().unwrap().issued.len(), 1);
+    }
+
+    #[test]
+    fn one_peer_cannot_take_every_nonce() {
+        let nonces = NonceIssuer::new(Duration::from_secs(60), 4, 2);
+        let greedy: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let first = nonces.issue(Some(greedy)).unwrap();
+        nonces.issue(Some(greedy)).unwrap();
+        assert!(nonces.issue(Some(greedy)).is_none());
+        assert!(nonces.issue(Some(other)).is_some());
+
+        nonces.take(&first).unwrap();
+        assert!(nonces.issue(Some(greedy)).is_some());
+    }
+
+    #[test]
+    fn taken_nonces_do_not_pile_up_in_the_expiry_heap() {
+        let nonces = NonceIssuer::new(Duration::from_secs(60), 4, 4);
+        for _ in 0..1000 {
+            let nonce = nonces.issue(None).unwrap();
+            nonces.take(&nonce).unwrap();
+        }
+        let state = nonces.state.lock().unwrap();
+        assert!(state.issued.is_empty());
+        assert!(state.expiries.len() <= 65, "{}", state.expiries.len());
+    }
+
+    #[test]
+    fn seen_set_drops_expired_digests_in_order() {
+        let seen = InMemorySeenSet::new();
+        let now = SystemTime::now();
+        let late = submission_digest("default", &inputs(&[1]));
+        let early = submission_digest("default", &inputs(&[2]));
+        assert!(seen.insert(late, now + Duration::from_secs(60)));
+        assert!(seen.insert(early, now - Duration::from_secs(1)));
+        assert!(seen.insert(submission_digest("default", &inputs(&[3])), now));
+
+        let state = seen.state.lock().unwrap();
+        assert_eq!(state.entries.len(), 2);
+        assert!(state.entries.contains_key(&late));
+    }
+}
@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Version of the wire protocol spoken between `ClientApp` and `ServerApp`.
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const CURVE: &str = "bls12-381";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum VerificationResponse {
     Valid {
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -9,7 +14,80 @@ pub enum VerificationResponse {
     Invalid {
         reason: String,
     },
+    Malformed {
+        reason: MalformedReason,
+        message: String,
+    },
+    Unauthorized {
+        reason: String,
+    },
+    Rejected {
+        reason: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        violation: Option<PolicyViolation>,
+    },
+    Duplicate {},
+    /// The nonce is unknown, expired or already used. Resending the proof with a fresh nonce may
+    /// still succeed.
+    StaleNonce {},
+    Retry {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after: Option<u64>,
+    },
+    RateLimited {
+        retry_after: u64,
+    },
+    Banned {
+        reason: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after: Option<u64>,
+    },
     Error {
         error: String,
     },
 }
+
+/// Why a request was refused before its proof was checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MalformedReason {
+    PayloadTooLarge,
+    InvalidJson,
+    InvalidBase64,
+    ProofLength,
+    InvalidProof,
+    InputCount,
+    NonCanonicalInput,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyViolation {
+    pub rule: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NonceResponse {
+    pub nonce: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReloadResponse {
+    /// Circuits whose verification key changed.
+    pub reloaded: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub protocol_version: u32,
+    pub curve: String,
+    pub circuits: BTreeMap<String, CircuitInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitInfo {
+    pub key_fingerprint: String,
+    pub public_inputs: usize,
+}
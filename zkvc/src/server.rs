@@ -1,196 +1,2128 @@
-use actix_web::{web, App, HttpResponse, HttpServer};
-use ark_bls12_381::{Bls12_381, Fr};
-use ark_groth16::{Groth16, Proof, VerifyingKey};
-use ark_serialize::CanonicalDeserialize;
-use ark_snark::SNARK;
-use base64::{engine::general_purpose::STANDARD, Engine as _};
+use actix_web::{
+    dev::{self, Service, ServiceRequest},
+    error::{InternalError, JsonPayloadError, PayloadError},
+    http::header,
+    web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer,
+};
+use ark_bls12_381::Fr;
+use futures_core::Stream;
 use log::{debug, error, info, warn};
-use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, sync::Arc, time::Instant};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::watch;
 
 use crate::{
-    circuit::ProofRequest,
-    response::VerificationResponse,
-    utils::{field_from_string, VERIFY_PATH},
+    admin::{AdminStatus, BanQuery, ClientSummary, ClientTracker, SubmissionsQuery, AUDIT_TARGET},
+    challenge::{ChallengeProvider, ChallengeQuery},
+    circuit::{ProofRequest, DEFAULT_CIRCUIT_ID},
+    handler::ProofHandler,
+    ledger::{FileLedger, Ledger, LedgerEntry},
+    limit::{Limiter, RateLimitConfig},
+    metrics::{Metrics, UNAUTHENTICATED_LABEL, UNKNOWN_CIRCUIT_LABEL},
+    policy::InputPolicy,
+    replay::SeenSet,
+    response::{MalformedReason, NonceResponse, ReloadResponse, ServerInfo, VerificationResponse},
+    tls::{self, TlsConfig},
+    utils::{
+        constant_time_eq, ADMIN_PATH, CHALLENGE_PATH, INFO_PATH, METRICS_PATH, NONCE_PATH,
+        VERIFY_BATCH_PATH, VERIFY_PATH,
+    },
+    verifier::{Verifier, VerifierConfig},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServerConfig {
     pub listen_address: String,
-    pub verification_key_path: PathBuf,
+    /// Verification key files by circuit id. Configs written before circuits had ids name a single
+    /// `verification_key_path`, which is read as the key of `DEFAULT_CIRCUIT_ID`.
+    #[serde(
+        alias = "verification_key_path",
+        deserialize_with = "deserialize_verification_keys"
+    )]
+    pub verification_keys: HashMap<String, PathBuf>,
+    pub replay_retention_secs: u64,
+    pub require_nonce: bool,
+    /// How long a nonce issued at `/nonce` stays valid if no proof uses it.
+    pub nonce_ttl_secs: u64,
+    /// Nonces issued at `/nonce` that may be outstanding at once. Further requests are answered
+    /// with 503 until older nonces are used or expire.
+    pub max_outstanding_nonces: usize,
+    /// Nonces that may be outstanding at once for a single peer address, so one peer cannot take
+    /// every slot.
+    pub max_nonces_per_peer: usize,
+    pub max_request_bytes: usize,
+    pub max_batch_size: usize,
+    pub verify_concurrency: usize,
+    pub verify_queue_depth: usize,
+    pub verify_retry_after_secs: u64,
+    pub ledger_path: Option<PathBuf>,
+    pub metrics_enabled: bool,
+    /// Label the metrics of up to this many clients by the id they claim even when their requests
+    /// are not signed, for deployments without `client_keys`. The ids are not verified, so any
+    /// caller can take these labels; later ids are counted under `unauthenticated`. Signed clients
+    /// are always labelled.
+    pub metrics_claimed_client_labels: usize,
+    pub rate_limit: RateLimitConfig,
+    /// Hex-encoded ed25519 public keys by client id. Requests naming a registered client must be
+    /// signed with its key.
+    pub client_keys: HashMap<String, String>,
+    /// Refuse requests from clients without a registered key.
+    pub require_signatures: bool,
+    /// Serve over HTTPS instead of plain HTTP.
+    pub tls: Option<TlsConfig>,
+    /// Bearer token for the `/admin` routes, which are not served when unset.
+    pub admin_token: Option<String>,
+    /// Re-read the verification key files at this interval and swap in any that changed.
+    pub key_reload_interval_secs: Option<u64>,
+    /// How long proofs for a replaced verification key are still accepted.
+    pub key_grace_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let verifier = VerifierConfig::default();
+        Self {
+            listen_address: String::new(),
+            verification_keys: verifier.verification_keys,
+            replay_retention_secs: verifier.replay_retention_secs,
+            require_nonce: verifier.require_nonce,
+            nonce_ttl_secs: verifier.nonce_ttl_secs,
+            max_outstanding_nonces: verifier.max_outstanding_nonces,
+            max_nonces_per_peer: verifier.max_nonces_per_peer,
+            max_request_bytes: 64 * 1024,
+            max_batch_size: 128,
+            verify_concurrency: verifier.verify_concurrency,
+            verify_queue_depth: verifier.verify_queue_depth,
+            verify_retry_after_secs: verifier.verify_retry_after_secs,
+            ledger_path: None,
+            metrics_enabled: false,
+            metrics_claimed_client_labels: 0,
+            rate_limit: RateLimitConfig::default(),
+            client_keys: verifier.client_keys,
+            require_signatures: verifier.require_signatures,
+            tls: None,
+            admin_token: None,
+            key_reload_interval_secs: None,
+            key_grace_secs: verifier.key_grace_secs,
+        }
+    }
+}
+
+impl From<&ServerConfig> for VerifierConfig {
+    fn from(config: &ServerConfig) -> Self {
+        Self {
+            verification_keys: config.verification_keys.clone(),
+            replay_retention_secs: config.replay_retention_secs,
+            require_nonce: config.require_nonce,
+            nonce_ttl_secs: config.nonce_ttl_secs,
+            max_outstanding_nonces: config.max_outstanding_nonces,
+            max_nonces_per_peer: config.max_nonces_per_peer,
+            verify_concurrency: config.verify_concurrency,
+            verify_queue_depth: config.verify_queue_depth,
+            verify_retry_after_secs: config.verify_retry_after_secs,
+            client_keys: config.client_keys.clone(),
+            require_signatures: config.require_signatures,
+            key_grace_secs: config.key_grace_secs,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn new(
+        listen_address: impl Into<String>,
+        verification_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            listen_address: listen_address.into(),
+            ..Default::default()
+        }
+        .with_circuit(DEFAULT_CIRCUIT_ID, verification_key_path)
+    }
+
+    pub fn with_circuit(
+        mut self,
+        circuit_id: impl Into<String>,
+        verification_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.verification_keys
+            .insert(circuit_id.into(), verification_key_path.into());
+        self
+    }
 }
 
-pub struct ServerApp<VP, IP, EP>
+fn deserialize_verification_keys<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, PathBuf>, D::Error>
 where
-    VP: Fn(&str, &[Fr]) -> Result<(), anyhow::Error> + Send + Sync + 'static,
-    IP: Fn(&str, &str) -> Result<(), anyhow::Error> + Send + Sync + 'static,
-    EP: Fn(&str, &anyhow::Error) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    D: Deserializer<'de>,
 {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum VerificationKeys {
+        ByCircuit(HashMap<String, PathBuf>),
+        Single(PathBuf),
+    }
+
+    Ok(match VerificationKeys::deserialize(deserializer)? {
+        VerificationKeys::ByCircuit(keys) => keys,
+        VerificationKeys::Single(path) => HashMap::from([(DEFAULT_CIRCUIT_ID.to_string(), path)]),
+    })
+}
+
+pub struct ServerApp {
     config: ServerConfig,
-    verification_key: Arc<VerifyingKey<Bls12_381>>,
-    valid_proof_handler: Option<VP>,
-    invalid_proof_handler: Option<IP>,
-    error_handler: Option<EP>,
+    verifier: Verifier,
+    ledger: Option<Arc<dyn Ledger>>,
+    metrics: Option<Arc<Metrics>>,
+    limiter: Limiter,
+    tls: Option<rustls::ServerConfig>,
+    clients: ClientTracker,
+    paused: AtomicBool,
+    /// Number of verifications being handled, so shutting down can wait for them.
+    in_flight: watch::Sender<usize>,
 }
 
-impl<VP, IP, EP> ServerApp<VP, IP, EP>
-where
-    VP: Fn(&str, &[Fr]) -> Result<(), anyhow::Error> + Send + Sync + 'static,
-    IP: Fn(&str, &str) -> Result<(), anyhow::Error> + Send + Sync + 'static,
-    EP: Fn(&str, &anyhow::Error) -> Result<(), anyhow::Error> + Send + Sync + 'static,
-{
+impl ServerApp {
     pub fn new(config: ServerConfig) -> Result<Self, anyhow::Error> {
         debug!("Creating new ServerApp instance");
-        let start = Instant::now();
-        let vk_bytes = std::fs::read(&config.verification_key_path)?;
-        let vk = VerifyingKey::deserialize_unchecked(&*vk_bytes)?;
-        info!(
-            "Verification key loaded successfully in {:?}",
-            start.elapsed()
-        );
+        let metrics = if config.metrics_enabled {
+            Some(Arc::new(Metrics::new(
+                config.metrics_claimed_client_labels,
+            )?))
+        } else {
+            None
+        };
+        let verifier = Verifier::with_metrics(&VerifierConfig::from(&config), metrics.clone())?;
+
+        let ledger = match &config.ledger_path {
+            Some(path) => {
+                info!("Recording submissions to {}", path.display());
+                Some(Arc::new(FileLedger::open(path)?) as Arc<dyn Ledger>)
+            }
+            None => None,
+        };
+
+        let tls = match &config.tls {
+            Some(tls_config) => {
+                let server_config = tls::server_config(tls_config)?;
+                info!(
+                    "TLS enabled with certificate {}{}",
+                    tls_config.cert_path.display(),
+                    if tls_config.client_ca_path.is_some() {
+                        ", client certificates required"
+                    } else {
+                        ""
+                    }
+                );
+                Some(server_config)
+            }
+            None => None,
+        };
 
         Ok(Self {
+            verifier,
+            ledger,
+            metrics,
+            limiter: Limiter::new(config.rate_limit.clone()),
+            tls,
+            clients: ClientTracker::new(),
+            paused: AtomicBool::new(false),
+            in_flight: watch::Sender::new(0),
             config,
-            verification_key: Arc::new(vk),
-            valid_proof_handler: None,
-            invalid_proof_handler: None,
-            error_handler: None,
         })
     }
 
-    pub fn with_valid_proof_handler(mut self, handler: VP) -> Self {
-        self.valid_proof_handler = Some(handler);
-        self
+    /// Sets the handler of every circuit without its own. Call it before the `with_*_handler`
+    /// builders: it replaces closures registered earlier, while closures registered afterwards
+    /// only override the matching method of `handler`.
+    pub fn with_handler(self, handler: impl ProofHandler) -> Self {
+        self.map_verifier(|verifier| verifier.with_handler(handler))
+    }
+
+    pub fn with_circuit_handler(
+        self,
+        circuit_id: impl Into<String>,
+        handler: impl ProofHandler,
+    ) -> Self {
+        self.map_verifier(|verifier| verifier.with_circuit_handler(circuit_id, handler))
+    }
+
+    pub fn with_valid_proof_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(&str, &[Fr]) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        self.map_verifier(|verifier| verifier.with_valid_proof_handler(f))
+    }
+
+    pub fn with_invalid_proof_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(&str, &str) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        self.map_verifier(|verifier| verifier.with_invalid_proof_handler(f))
+    }
+
+    pub fn with_error_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(&str, &anyhow::Error) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        self.map_verifier(|verifier| verifier.with_error_handler(f))
+    }
+
+    pub fn with_async_valid_proof_handler<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(String, Vec<Fr>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.map_verifier(|verifier| verifier.with_async_valid_proof_handler(f))
+    }
+
+    pub fn with_async_invalid_proof_handler<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(String, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.map_verifier(|verifier| verifier.with_async_invalid_proof_handler(f))
+    }
+
+    pub fn with_async_error_handler<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(String, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.map_verifier(|verifier| verifier.with_async_error_handler(f))
+    }
+
+    pub fn with_challenge_provider(self, provider: impl ChallengeProvider) -> Self {
+        self.map_verifier(|verifier| verifier.with_challenge_provider(provider))
+    }
+
+    pub fn with_circuit_challenge_provider(
+        self,
+        circuit_id: impl Into<String>,
+        provider: impl ChallengeProvider,
+    ) -> Self {
+        self.map_verifier(|verifier| verifier.with_circuit_challenge_provider(circuit_id, provider))
     }
 
-    pub fn with_invalid_proof_handler(mut self, handler: IP) -> Self {
-        self.invalid_proof_handler = Some(handler);
+    pub fn with_input_policy(self, policy: InputPolicy) -> Self {
+        self.map_verifier(|verifier| verifier.with_input_policy(policy))
+    }
+
+    pub fn with_circuit_input_policy(
+        self,
+        circuit_id: impl Into<String>,
+        policy: InputPolicy,
+    ) -> Self {
+        self.map_verifier(|verifier| verifier.with_circuit_input_policy(circuit_id, policy))
+    }
+
+    pub fn with_seen_set(self, seen: impl SeenSet) -> Self {
+        self.map_verifier(|verifier| verifier.with_seen_set(seen))
+    }
+
+    pub fn with_client_key(
+        self,
+        client_id: impl Into<String>,
+        key: ed25519_dalek::VerifyingKey,
+    ) -> Self {
+        self.map_verifier(|verifier| verifier.with_client_key(client_id, key))
+    }
+
+    fn map_verifier(mut self, f: impl FnOnce(Verifier) -> Verifier) -> Self {
+        self.verifier = f(self.verifier);
         self
     }
 
-    pub fn with_error_handler(mut self, handler: EP) -> Self {
-        self.error_handler = Some(handler);
+    pub fn with_ledger(mut self, ledger: impl Ledger) -> Self {
+        self.ledger = Some(Arc::new(ledger));
         self
     }
 
-    fn verify(&self, request: &ProofRequest) -> Result<bool, anyhow::Error> {
-        debug!("Verifying proof");
-        let start = Instant::now();
+    async fn process(&self, request: &ProofRequest, peer: Option<IpAddr>) -> VerificationResponse {
+        let _in_flight = InFlightGuard::enter(&self.in_flight);
+        let received_at = SystemTime::now();
+        let (response, authenticated) = match self.admit(request, peer) {
+            Ok(authenticated) => (self.verifier.verify(request).await, authenticated),
+            Err(response) => (response, false),
+        };
+        let entry = self.record(request, peer, received_at, authenticated, &response);
+        self.persist(entry.into_iter().collect()).await;
+        response
+    }
 
-        let proof_bytes = STANDARD.decode(&request.proof.0)?;
-        let proof: Proof<Bls12_381> = Proof::deserialize_uncompressed(&*proof_bytes)?;
-        let inputs: Vec<Fr> = request
-            .public_inputs
+    async fn process_batch(
+        &self,
+        requests: &[ProofRequest],
+        peer: Option<IpAddr>,
+    ) -> Vec<VerificationResponse> {
+        let _in_flight = InFlightGuard::enter(&self.in_flight);
+        let received_at = SystemTime::now();
+        let admissions: Vec<_> = requests
+            .iter()
+            .map(|request| self.admit(request, peer))
+            .collect();
+        let admitted: Vec<&ProofRequest> = requests
             .iter()
-            .map(|s| field_from_string(s))
-            .collect::<Result<Vec<_>, _>>()?;
-        debug!("Inputs: {:?}", inputs);
+            .zip(&admissions)
+            .filter(|(_, admission)| admission.is_ok())
+            .map(|(request, _)| request)
+            .collect();
+        let mut verified = self.verifier.verify_all(&admitted).await.into_iter();
+
+        let mut responses = Vec::with_capacity(requests.len());
+        let mut entries = Vec::new();
+        for (request, admission) in requests.iter().zip(admissions) {
+            let (response, authenticated) = match admission {
+                Ok(authenticated) => {
+                    let response = verified
+                        .next()
+                        .expect("every admitted request has a response");
+                    (response, authenticated)
+                }
+                Err(response) => (response, false),
+            };
+            entries.extend(self.record(request, peer, received_at, authenticated, &response));
+            responses.push(response);
+        }
+        self.persist(entries).await;
+        responses
+    }
+
+    /// Applies the pause switch and the rate limits. Returns whether the request is signed by the
+    /// registered key of the client it names.
+    ///
+    /// Only a checked signature ties a submission to the client id it names, so the client's own
+    /// limits and allow-list entry only apply after that check; until then the submission is
+    /// charged to its peer address.
+    fn admit(
+        &self,
+        request: &ProofRequest,
+        peer: Option<IpAddr>,
+    ) -> Result<bool, VerificationResponse> {
+        if self.paused.load(Ordering::Relaxed) {
+            debug!(
+                "Acceptance is paused, asking client {} to retry later",
+                request.client_id
+            );
+            return Err(VerificationResponse::Retry {
+                retry_after: Some(self.config.verify_retry_after_secs),
+            });
+        }
+        self.limiter
+            .admit(&request.client_id, peer)
+            .and_then(|()| {
+                let authenticated = self.verifier.is_authenticated(request);
+                if authenticated {
+                    self.limiter.admit_authenticated(&request.client_id, peer)?;
+                }
+                Ok(authenticated)
+            })
+            .inspect_err(|response| {
+                debug!(
+                    "Refusing submission from client {} ({:?}): {:?}",
+                    request.client_id, peer, response
+                );
+            })
+    }
+
+    /// Updates metrics, strikes and the client view, and returns the ledger entry to `persist`.
+    /// `authenticated` is what `admit` returned, `false` for refused submissions.
+    fn record(
+        &self,
+        request: &ProofRequest,
+        peer: Option<IpAddr>,
+        received_at: SystemTime,
+        authenticated: bool,
+        response: &VerificationResponse,
+    ) -> Option<LedgerEntry> {
+        // The ids of unauthenticated submissions are neither tracked for the admin API nor banned,
+        // and only used as metric labels up to `metrics_claimed_client_labels`.
+        let client_label = if authenticated {
+            request.client_id.as_str()
+        } else {
+            UNAUTHENTICATED_LABEL
+        };
+        if let Some(metrics) = &self.metrics {
+            let client_label = metrics.client_label(&request.client_id, authenticated);
+            let circuit_id = if self.verifier.has_circuit(&request.circuit_id) {
+                request.circuit_id.as_str()
+            } else {
+                UNKNOWN_CIRCUIT_LABEL
+            };
+            metrics.observe_response(client_label, circuit_id, response);
+        }
+
+        // Strikes against an unauthenticated client id only count towards its peer address, so
+        // forged requests cannot get someone else banned.
+        if matches!(
+            response,
+            VerificationResponse::Invalid { .. }
+                | VerificationResponse::Malformed { .. }
+                | VerificationResponse::Unauthorized { .. }
+        ) {
+            let client_id = authenticated.then_some(request.client_id.as_str());
+            if self.limiter.record_invalid(client_id, peer) {
+                warn!(
+                    "Banning client {} ({:?}) after repeated invalid submissions",
+                    request.client_id, peer
+                );
+            }
+        }
 
-        let result = Groth16::<Bls12_381>::verify(&self.verification_key, &inputs, &proof)?;
-        debug!("Proof verification completed in {:?}", start.elapsed());
-        info!("Proof verification result: {}", result);
-        Ok(result)
+        // Refused submissions are not recorded, so a flood cannot grow the ledger.
+        if matches!(
+            response,
+            VerificationResponse::RateLimited { .. } | VerificationResponse::Banned { .. }
+        ) {
+            return None;
+        }
+        self.clients.observe(client_label, response);
+
+        // Without a ledger there is nothing to persist.
+        self.ledger.as_ref()?;
+        Some(LedgerEntry {
+            client_id: request.client_id.clone(),
+            circuit_id: request.circuit_id.clone(),
+            public_inputs: request.public_inputs.clone(),
+            proof: request.proof.0.clone(),
+            nonce: request.nonce.clone(),
+            verdict: response.clone(),
+            received_at,
+            decided_at: SystemTime::now(),
+            key_fingerprint: self.verifier.key_fingerprint(&request.circuit_id),
+        })
+    }
+
+    /// Writes `entries` to the ledger on the blocking thread pool.
+    async fn persist(&self, entries: Vec<LedgerEntry>) {
+        let Some(ledger) = &self.ledger else {
+            return;
+        };
+        if entries.is_empty() {
+            return;
+        }
+        let ledger = Arc::clone(ledger);
+        let written = tokio::task::spawn_blocking(move || {
+            for entry in &entries {
+                if let Err(e) = ledger.record(entry) {
+                    error!(
+                        "Failed to record submission from client {} in the ledger: {}",
+                        entry.client_id, e
+                    );
+                }
+            }
+        })
+        .await;
+        if let Err(e) = written {
+            error!("Ledger writer failed: {}", e);
+        }
     }
 
     async fn verify_handler(
+        http_request: HttpRequest,
         request: web::Json<ProofRequest>,
         app: web::Data<Arc<Self>>,
     ) -> HttpResponse {
         let start = Instant::now();
         debug!(
-            "Received verification request from client {}",
-            request.client_id
+            "Received verification request from client {} for circuit {}",
+            request.client_id, request.circuit_id
         );
 
-        let inputs: Vec<Fr> = match request
-            .public_inputs
-            .iter()
-            .map(|s| field_from_string(s))
-            .collect::<Result<Vec<_>, _>>()
-        {
-            Ok(inputs) => inputs,
-            Err(e) => {
-                error!("Failed to parse inputs: {}", e);
-                if let Some(handler) = &app.error_handler {
-                    if let Err(handler_err) = handler(&request.client_id, &e) {
-                        error!("Error handler failed: {}", handler_err);
-                    }
-                }
-                return HttpResponse::InternalServerError().json(VerificationResponse::Error {
-                    error: e.to_string(),
-                });
-            }
-        };
+        let peer = http_request.peer_addr().map(|addr| addr.ip());
+        let response = app.process(&request, peer).await;
 
-        let response = match app.verify(&request) {
-            Ok(true) => {
-                info!(
-                    "Proof verified successfully for client {}",
-                    request.client_id
-                );
-                if let Some(handler) = &app.valid_proof_handler {
-                    if let Err(e) = handler(&request.client_id, &inputs) {
-                        error!("Valid proof handler failed: {}", e);
-                        return HttpResponse::InternalServerError().json(
-                            VerificationResponse::Error {
-                                error: e.to_string(),
-                            },
-                        );
-                    }
+        debug!("Total request handling time: {:?}", start.elapsed());
+        http_response(response)
+    }
+
+    async fn verify_batch_handler(
+        http_request: HttpRequest,
+        requests: web::Json<Vec<ProofRequest>>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        let start = Instant::now();
+        debug!(
+            "Received batch verification request with {} proofs",
+            requests.len()
+        );
+
+        if requests.len() > app.config.max_batch_size {
+            warn!(
+                "Batch of {} proofs exceeds the limit of {}",
+                requests.len(),
+                app.config.max_batch_size
+            );
+            return http_response(VerificationResponse::Malformed {
+                reason: MalformedReason::PayloadTooLarge,
+                message: format!(
+                    "Batch of {} proofs exceeds the limit of {}",
+                    requests.len(),
+                    app.config.max_batch_size
+                ),
+            });
+        }
+
+        let peer = http_request.peer_addr().map(|addr| addr.ip());
+        let responses = app.process_batch(&requests, peer).await;
+
+        debug!("Total batch handling time: {:?}", start.elapsed());
+        HttpResponse::Ok().json(responses)
+    }
+
+    async fn challenge_handler(
+        query: web::Query<ChallengeQuery>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        let circuit_id = query.circuit_id.as_deref().unwrap_or(DEFAULT_CIRCUIT_ID);
+        match app.verifier.issue_challenge(circuit_id) {
+            Some(issued) => match issued {
+                Ok(value) => HttpResponse::Ok().json(value),
+                Err(e) => {
+                    error!(
+                        "Failed to issue challenge for circuit {}: {}",
+                        circuit_id, e
+                    );
+                    HttpResponse::InternalServerError().body(e.to_string())
                 }
-                HttpResponse::Ok().json(VerificationResponse::Valid {
-                    result: Some(request.public_inputs.clone()),
+            },
+            None => HttpResponse::NotFound()
+                .body(format!("No challenge provider for circuit {}", circuit_id)),
+        }
+    }
+
+    async fn nonce_handler(http_request: HttpRequest, app: web::Data<Arc<Self>>) -> HttpResponse {
+        let peer = http_request.peer_addr().map(|addr| addr.ip());
+        if let Err(response) = app.limiter.admit_nonce(peer) {
+            debug!("Refusing nonce request from {:?}: {:?}", peer, response);
+            return http_response(response);
+        }
+        match app.verifier.issue_nonce_for(peer) {
+            Ok(nonce) => HttpResponse::Ok().json(NonceResponse { nonce }),
+            Err(e) => {
+                warn!("Refusing nonce request from {:?}: {}", peer, e);
+                http_response(VerificationResponse::Retry {
+                    retry_after: Some(app.config.verify_retry_after_secs),
                 })
             }
-            Ok(false) => {
-                warn!("Invalid proof received from client {}", request.client_id);
-                let reason = "Proof verification failed".to_string();
-                if let Some(handler) = &app.invalid_proof_handler {
-                    if let Err(e) = handler(&request.client_id, &reason) {
-                        error!("Invalid proof handler failed: {}", e);
-                        return HttpResponse::InternalServerError().json(
-                            VerificationResponse::Error {
-                                error: e.to_string(),
-                            },
-                        );
-                    }
-                }
-                HttpResponse::BadRequest().json(VerificationResponse::Invalid { reason })
+        }
+    }
+
+    async fn info_handler(app: web::Data<Arc<Self>>) -> HttpResponse {
+        HttpResponse::Ok().json(app.info())
+    }
+
+    async fn metrics_handler(app: web::Data<Arc<Self>>) -> HttpResponse {
+        let Some(metrics) = &app.metrics else {
+            return HttpResponse::NotFound().finish();
+        };
+        metrics.queue_depth.set(app.verifier.pending() as i64);
+        match metrics.encode() {
+            Ok(body) => HttpResponse::Ok()
+                .content_type(prometheus::TEXT_FORMAT)
+                .body(body),
+            Err(e) => {
+                error!("Failed to encode metrics: {}", e);
+                HttpResponse::InternalServerError().body(e.to_string())
             }
+        }
+    }
+
+    async fn reload_handler(http_request: HttpRequest, app: web::Data<Arc<Self>>) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, "reload verification keys");
+        match app.verifier.reload().await {
+            Ok(reloaded) => HttpResponse::Ok().json(ReloadResponse { reloaded }),
             Err(e) => {
-                error!("Verification error for client {}: {}", request.client_id, e);
-                if let Some(handler) = &app.error_handler {
-                    if let Err(handler_err) = handler(&request.client_id, &e) {
-                        error!("Error handler failed: {}", handler_err);
-                    }
-                }
+                error!("Failed to reload verification keys: {}", e);
                 HttpResponse::InternalServerError().json(VerificationResponse::Error {
                     error: e.to_string(),
                 })
             }
+        }
+    }
+
+    async fn status_handler(http_request: HttpRequest, app: web::Data<Arc<Self>>) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, "read status");
+        HttpResponse::Ok().json(AdminStatus {
+            paused: app.paused.load(Ordering::Relaxed),
+            pending_verifications: app.verifier.pending(),
+            tracked_clients: app.clients.len(),
+            revoked_clients: app.limiter.revoked(),
+        })
+    }
+
+    async fn pause_handler(http_request: HttpRequest, app: web::Data<Arc<Self>>) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, "pause acceptance");
+        app.paused.store(true, Ordering::Relaxed);
+        HttpResponse::NoContent().finish()
+    }
+
+    async fn resume_handler(http_request: HttpRequest, app: web::Data<Arc<Self>>) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, "resume acceptance");
+        app.paused.store(false, Ordering::Relaxed);
+        HttpResponse::NoContent().finish()
+    }
+
+    async fn clients_handler(http_request: HttpRequest, app: web::Data<Arc<Self>>) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, "list clients");
+        let summaries: Vec<ClientSummary> = app
+            .clients
+            .summaries()
+            .into_iter()
+            .map(|summary| ClientSummary {
+                banned_for: app
+                    .limiter
+                    .banned_for(&summary.client_id)
+                    .map(|remaining| remaining.as_secs().max(1)),
+                revoked: app.limiter.is_revoked(&summary.client_id),
+                ..summary
+            })
+            .collect();
+        HttpResponse::Ok().json(summaries)
+    }
+
+    async fn submissions_handler(
+        http_request: HttpRequest,
+        query: web::Query<SubmissionsQuery>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, &format!("query submissions {:?}", query.0));
+        let Some(ledger) = &app.ledger else {
+            return HttpResponse::NotFound().body("No ledger configured");
         };
+        let ledger = Arc::clone(ledger);
+        let query = query.to_ledger_query();
+        let entries = tokio::task::spawn_blocking(move || ledger.query(&query))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|entries| entries);
+        match entries {
+            Ok(entries) => HttpResponse::Ok().json(entries),
+            Err(e) => {
+                error!("Failed to query the ledger: {}", e);
+                HttpResponse::InternalServerError().body(e.to_string())
+            }
+        }
+    }
 
-        debug!("Total request handling time: {:?}", start.elapsed());
-        response
+    async fn ban_handler(
+        http_request: HttpRequest,
+        client_id: web::Path<String>,
+        query: web::Query<BanQuery>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        let duration = Duration::from_secs(
+            query
+                .duration_secs
+                .unwrap_or(app.config.rate_limit.ban_duration_secs),
+        );
+        audit(
+            &http_request,
+            &format!("ban client {} for {:?}", client_id, duration),
+        );
+        app.limiter.ban(&client_id, duration);
+        HttpResponse::NoContent().finish()
     }
 
-    pub async fn run(self) -> std::io::Result<()> {
+    async fn revoke_handler(
+        http_request: HttpRequest,
+        client_id: web::Path<String>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, &format!("revoke client {}", client_id));
+        app.limiter.revoke(&client_id);
+        HttpResponse::NoContent().finish()
+    }
+
+    async fn unban_handler(
+        http_request: HttpRequest,
+        client_id: web::Path<String>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(&http_request, &format!("unban client {}", client_id));
+        if app.limiter.unban(&client_id) {
+            HttpResponse::NoContent().finish()
+        } else {
+            HttpResponse::NotFound().body(format!("Client {} is not banned", client_id))
+        }
+    }
+
+    async fn rotate_challenge_handler(
+        http_request: HttpRequest,
+        circuit_id: web::Path<String>,
+        app: web::Data<Arc<Self>>,
+    ) -> HttpResponse {
+        if let Err(response) = app.authorize_admin(&http_request) {
+            return response;
+        }
+        audit(
+            &http_request,
+            &format!("rotate challenge for circuit {}", circuit_id),
+        );
+        if app.verifier.rotate_challenge(&circuit_id).await {
+            HttpResponse::NoContent().finish()
+        } else {
+            HttpResponse::NotFound()
+                .body(format!("No challenge provider for circuit {}", circuit_id))
+        }
+    }
+
+    fn authorize_admin(&self, request: &HttpRequest) -> Result<(), HttpResponse> {
+        let Some(token) = &self.config.admin_token else {
+            return Err(HttpResponse::NotFound().finish());
+        };
+        let provided = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes()))
+        {
+            return Ok(());
+        }
+        warn!(
+            target: AUDIT_TARGET,
+            "Refused admin request from {}: {} {}",
+            audit_peer(request),
+            request.method(),
+            request.path()
+        );
+        Err(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .json(VerificationResponse::Unauthorized {
+                reason: "Missing or wrong admin token".to_string(),
+            }))
+    }
+
+    /// Makes the body of `request` count towards the request-size histogram as it is read, so
+    /// chunked bodies without a `Content-Length` are measured too.
+    fn count_request_size(
+        &self,
+        endpoint: &'static str,
+        mut request: ServiceRequest,
+    ) -> ServiceRequest {
+        if let Some(metrics) = &self.metrics {
+            let counted = CountedPayload {
+                inner: request.take_payload(),
+                read: 0,
+                metrics: Arc::clone(metrics),
+                endpoint,
+            };
+            request.set_payload(dev::Payload::Stream {
+                payload: Box::pin(counted),
+            });
+        }
+        request
+    }
+
+    /// Turns the server into a service that can be mounted inside an existing actix application.
+    pub fn into_service(self) -> VerifierService {
+        VerifierService {
+            app: Arc::new(self),
+        }
+    }
+
+    /// Binds the listen address and starts serving in the background.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn start(self) -> std::io::Result<ServerHandle> {
         let address = self.config.listen_address.clone();
         info!("Starting server on {}", address);
 
-        let app = Arc::new(self);
-        HttpServer::new(move || {
-            let app = web::Data::new(Arc::clone(&app));
-            App::new()
-                .app_data(app)
-                .route(VERIFY_PATH, web::post().to(Self::verify_handler))
+        let tls = self.tls.clone();
+        let in_flight = self.in_flight.subscribe();
+        let service = self.into_service();
+        let server_service = service.clone();
+        let server =
+            HttpServer::new(move || App::new().configure(|cfg| server_service.configure(cfg)));
+        let server = match tls {
+            Some(tls) => server.bind_rustls_0_23(address, tls)?,
+            None => server.bind(address)?,
+        };
+
+        let local_addrs = server.addrs();
+        info!("Server listening on {:?}", local_addrs);
+
+        let server = server.run();
+        let server_handle = server.handle();
+        let watcher = service.spawn_key_watcher();
+        let task = tokio::spawn(async move {
+            let result = server.await;
+            if let Some(watcher) = watcher {
+                watcher.abort();
+            }
+            service.shutdown().await;
+            result
+        });
+
+        Ok(ServerHandle {
+            local_addrs,
+            server: server_handle,
+            in_flight,
+            task,
         })
-        .bind(address)?
-        .run()
-        .await
+    }
+
+    pub async fn run(self) -> std::io::Result<()> {
+        self.start()?.join().await
+    }
+
+    async fn shutdown(&self) {
+        wait_until_idle(self.in_flight.subscribe()).await;
+        self.verifier.drain().await;
+
+        if let Some(ledger) = &self.ledger {
+            let ledger = Arc::clone(ledger);
+            let flushed = tokio::task::spawn_blocking(move || ledger.flush())
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|flushed| flushed);
+            if let Err(e) = flushed {
+                error!("Failed to flush the ledger: {}", e);
+            }
+        }
+        self.verifier.shut_down_handlers().await;
+        info!("Server shut down");
     }
 
     pub fn get_listen_address(&self) -> &str {
         &self.config.listen_address
     }
+
+    pub fn get_circuit_ids(&self) -> impl Iterator<Item = &str> {
+        self.verifier.get_circuit_ids()
+    }
+
+    pub fn info(&self) -> ServerInfo {
+        self.verifier.info()
+    }
+
+    pub fn get_verifier(&self) -> &Verifier {
+        &self.verifier
+    }
+
+    pub fn get_ledger(&self) -> Option<&dyn Ledger> {
+        self.ledger.as_deref()
+    }
+}
+
+/// Verification routes of a `ServerApp`, for mounting inside an application that is already
+/// running its own `HttpServer`. Cloning is cheap and every clone shares the same state.
+///
+/// The TLS and listen address settings of `ServerConfig` are not used here; they belong to the
+/// surrounding server.
+#[derive(Clone)]
+pub struct VerifierService {
+    app: Arc<ServerApp>,
+}
+
+impl VerifierService {
+    /// Registers `/verify`, `/verify/batch`, `/challenge`, `/nonce`, `/info` and, when enabled,
+    /// `/metrics` and `/admin`. Each resource carries its own app data, so several services can share one
+    /// `App` as long as they are mounted under different prefixes.
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        let app = web::Data::new(Arc::clone(&self.app));
+        let request_limit = self.app.config.max_request_bytes;
+        let batch_limit = request_limit.saturating_mul(self.app.config.max_batch_size);
+
+        let verify_app = Arc::clone(&self.app);
+        let batch_app = Arc::clone(&self.app);
+
+        cfg.service(
+            web::resource(VERIFY_PATH)
+                .app_data(app.clone())
+                .app_data(json_config(request_limit))
+                .wrap_fn(move |request, service| {
+                    service.call(verify_app.count_request_size(VERIFY_PATH, request))
+                })
+                .route(web::post().to(ServerApp::verify_handler)),
+        )
+        .service(
+            web::resource(VERIFY_BATCH_PATH)
+                .app_data(app.clone())
+                .app_data(json_config(batch_limit))
+                .wrap_fn(move |request, service| {
+                    service.call(batch_app.count_request_size(VERIFY_BATCH_PATH, request))
+                })
+                .route(web::post().to(ServerApp::verify_batch_handler)),
+        )
+        .service(
+            web::resource(CHALLENGE_PATH)
+                .app_data(app.clone())
+                .route(web::get().to(ServerApp::challenge_handler)),
+        )
+        .service(
+            web::resource(NONCE_PATH)
+                .app_data(app.clone())
+                .route(web::get().to(ServerApp::nonce_handler)),
+        )
+        .service(
+            web::resource(INFO_PATH)
+                .app_data(app.clone())
+                .route(web::get().to(ServerApp::info_handler)),
+        );
+        if self.app.metrics.is_some() {
+            cfg.service(
+                web::resource(METRICS_PATH)
+                    .app_data(app.clone())
+                    .route(web::get().to(ServerApp::metrics_handler)),
+            );
+        }
+        if self.app.config.admin_token.is_some() {
+            cfg.service(
+                web::scope(ADMIN_PATH)
+                    .app_data(app)
+                    .route("/status", web::get().to(ServerApp::status_handler))
+                    .route("/reload", web::post().to(ServerApp::reload_handler))
+                    .route("/pause", web::post().to(ServerApp::pause_handler))
+                    .route("/resume", web::post().to(ServerApp::resume_handler))
+                    .route("/clients", web::get().to(ServerApp::clients_handler))
+                    .route(
+                        "/clients/{client_id}/ban",
+                        web::post().to(ServerApp::ban_handler),
+                    )
+                    .route(
+                        "/clients/{client_id}/revoke",
+                        web::post().to(ServerApp::revoke_handler),
+                    )
+                    .route(
+                        "/clients/{client_id}/unban",
+                        web::post().to(ServerApp::unban_handler),
+                    )
+                    .route(
+                        "/submissions",
+                        web::get().to(ServerApp::submissions_handler),
+                    )
+                    .route(
+                        "/challenges/{circuit_id}/rotate",
+                        web::post().to(ServerApp::rotate_challenge_handler),
+                    ),
+            );
+        }
+    }
+
+    /// The routes of `configure` under a path prefix such as `/zkvc`.
+    pub fn scope(&self, prefix: &str) -> actix_web::Scope {
+        web::scope(prefix).configure(|cfg| self.configure(cfg))
+    }
+
+    /// Starts re-reading the verification keys every `ServerConfig::key_reload_interval_secs`.
+    /// Returns `None` when that is unset. Must be called from within a Tokio runtime.
+    pub fn spawn_key_watcher(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let interval = Duration::from_secs(self.app.config.key_reload_interval_secs?.max(1));
+        let app = Arc::clone(&self.app);
+        Some(tokio::spawn(async move {
+            app.verifier.watch_keys(interval).await
+        }))
+    }
+
+    /// Waits for in-flight verifications, flushes the ledger and shuts the handlers down. Call
+    /// this once the surrounding server has stopped.
+    pub async fn shutdown(&self) {
+        self.app.shutdown().await;
+    }
+
+    pub fn server(&self) -> &ServerApp {
+        &self.app
+    }
+}
+
+/// Handle to a server started with `ServerApp::start`.
+pub struct ServerHandle {
+    local_addrs: Vec<SocketAddr>,
+    server: dev::ServerHandle,
+    in_flight: watch::Receiver<usize>,
+    task: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl ServerHandle {
+    /// The address the server is bound to, with the actual port when listening on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs[0]
+    }
+
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Stops accepting connections. A graceful stop lets in-flight verifications finish first.
+    pub async fn stop(&self, graceful: bool) {
+        info!("Stopping server (graceful: {})", graceful);
+        if graceful {
+            // Once its accept thread is gone an actix worker may exit before it sees the stop
+            // message, dropping connections whose request is still being handled.
+            self.server.pause().await;
+            wait_until_idle(self.in_flight.clone()).await;
+        }
+        self.server.stop(graceful).await;
+    }
+
+    /// Resolves once the server has stopped, in-flight verifications have drained, the ledger has
+    /// been flushed and the handlers have been shut down.
+    pub async fn join(self) -> std::io::Result<()> {
+        self.task.await.map_err(std::io::Error::other)?
+    }
+}
+
+/// How long shutting down waits for in-flight verifications, the same as actix's own shutdown
+/// timeout.
+const GRACEFUL_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn wait_until_idle(mut in_flight: watch::Receiver<usize>) {
+    let idle = in_flight.wait_for(|count| *count == 0);
+    if tokio::time::timeout(GRACEFUL_STOP_TIMEOUT, idle)
+        .await
+        .is_err()
+    {
+        warn!(
+            "Gave up waiting for {} in-flight verifications",
+            *in_flight.borrow()
+        );
+    }
+}
+
+/// Counts a verification as in flight until dropped.
+struct InFlightGuard<'a>(&'a watch::Sender<usize>);
+
+impl<'a> InFlightGuard<'a> {
+    fn enter(in_flight: &'a watch::Sender<usize>) -> Self {
+        in_flight.send_modify(|count| *count += 1);
+        Self(in_flight)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
+
+fn audit(request: &HttpRequest, action: &str) {
+    info!(
+        target: AUDIT_TARGET,
+        "Admin action from {}: {}",
+        audit_peer(request),
+        action
+    );
+}
+
+fn audit_peer(request: &HttpRequest) -> String {
+    request
+        .peer_addr()
+        .map_or_else(|| "unknown peer".to_string(), |addr| addr.to_string())
+}
+
+/// Request body that records how many bytes were read from it once it is dropped. Bodies refused
+/// before a byte was read, such as ones announcing a `Content-Length` over the limit, are not
+/// recorded.
+struct CountedPayload {
+    inner: dev::Payload,
+    read: usize,
+    metrics: Arc<Metrics>,
+    endpoint: &'static str,
+}
+
+impl Stream for CountedPayload {
+    type Item = Result<web::Bytes, PayloadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &polled {
+            self.read += chunk.len();
+        }
+        polled
+    }
+}
+
+impl Drop for CountedPayload {
+    fn drop(&mut self) {
+        if self.read > 0 {
+            self.metrics.observe_request_size(self.endpoint, self.read);
+        }
+    }
+}
+
+fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .error_handler(|err, _request| {
+            let reason = match &err {
+                JsonPayloadError::Overflow { .. }
+                | JsonPayloadError::OverflowKnownLength { .. } => MalformedReason::PayloadTooLarge,
+                _ => MalformedReason::InvalidJson,
+            };
+            warn!("Rejecting unreadable request: {}", err);
+            let response = http_response(VerificationResponse::Malformed {
+                reason,
+                message: err.to_string(),
+            });
+            InternalError::from_response(err, response).into()
+        })
+}
+
+fn http_response(response: VerificationResponse) -> HttpResponse {
+    let mut builder = match &response {
+        VerificationResponse::Valid { .. } => HttpResponse::Ok(),
+        VerificationResponse::Malformed {
+            reason: MalformedReason::PayloadTooLarge,
+            ..
+        } => HttpResponse::PayloadTooLarge(),
+        VerificationResponse::Invalid { .. } | VerificationResponse::Malformed { .. } => {
+            HttpResponse::BadRequest()
+        }
+        VerificationResponse::Unauthorized { .. } => HttpResponse::Unauthorized(),
+        VerificationResponse::Rejected { .. } => HttpResponse::UnprocessableEntity(),
+        VerificationResponse::Duplicate {} => HttpResponse::Conflict(),
+        VerificationResponse::StaleNonce {} => HttpResponse::PreconditionFailed(),
+        VerificationResponse::Retry { retry_after } => {
+            let mut builder = HttpResponse::ServiceUnavailable();
+            if let Some(retry_after) = retry_after {
+                builder.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+            }
+            builder
+        }
+        VerificationResponse::RateLimited { retry_after } => {
+            let mut builder = HttpResponse::TooManyRequests();
+            builder.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+            builder
+        }
+        VerificationResponse::Banned { retry_after, .. } => {
+            let mut builder = HttpResponse::Forbidden();
+            if let Some(retry_after) = retry_after {
+                builder.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+            }
+            builder
+        }
+        VerificationResponse::Error { .. } => HttpResponse::InternalServerError(),
+    };
+    builder.json(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler::{HandlerFuture, Verdict},
+        identity::{generate_signing_key, public_key_to_hex},
+        ledger::LedgerQuery,
+        limit::TokenBucketConfig,
+        test_utils::{request, server_config},
+    };
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+    };
+    use serde::de::DeserializeOwned;
+
+    async fn post<T: DeserializeOwned>(
+        handle: &ServerHandle,
+        path: &str,
+        body: &impl Serialize,
+    ) -> T {
+        reqwest::Client::new()
+            .post(format!("http://{}{}", handle.local_addr(), path))
+            .json(body)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    async fn get_text(handle: &ServerHandle, path: &str) -> String {
+        reqwest::get(format!("http://{}{}", handle.local_addr(), path))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// A request for `client_id` whose proof does not match its public inputs.
+    fn invalid_request(client_id: &str) -> ProofRequest {
+        let mut request = request(client_id, 3, 5);
+        request.public_inputs = vec!["16".to_string()];
+        request
+    }
+
+    #[tokio::test]
+    async fn only_authenticated_strikes_ban_the_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = generate_signing_key();
+        let mut config = server_config(dir.path());
+        config.rate_limit.ban_after_invalid = Some(2);
+        config.client_keys.insert(
+            "signed".to_string(),
+            public_key_to_hex(&key.verifying_key()),
+        );
+        let app = ServerApp::new(config).unwrap();
+        let peers: Vec<IpAddr> = (1..=4)
+            .map(|host| format!("192.0.2.{}", host).parse().unwrap())
+            .collect();
+
+        // Unsigned, and for "signed" also unauthorized: only the peer address is banned.
+        for (client_id, peer) in [("victim", peers[0]), ("signed", peers[1])] {
+            for _ in 0..2 {
+                app.process(&invalid_request(client_id), Some(peer)).await;
+            }
+            let response = app.process(&request(client_id, 3, 7), Some(peer)).await;
+            assert!(matches!(response, VerificationResponse::Banned { .. }));
+            assert!(app.limiter.banned_for(client_id).is_none());
+        }
+        let response = app.process(&request("victim", 3, 7), Some(peers[2])).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+
+        for _ in 0..2 {
+            let mut invalid = invalid_request("signed");
+            invalid.sign(&key);
+            app.process(&invalid, Some(peers[3])).await;
+        }
+        assert!(app.limiter.banned_for("signed").is_some());
+    }
+
+    #[tokio::test]
+    async fn claimed_client_ids_do_not_lift_or_drain_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = generate_signing_key();
+        let mut config = server_config(dir.path());
+        let bucket = TokenBucketConfig {
+            capacity: 1,
+            refill_per_sec: 0.001,
+        };
+        config.rate_limit.per_ip = Some(bucket.clone());
+        config.rate_limit.per_client = Some(bucket);
+        config
+            .rate_limit
+            .allow_clients
+            .insert("trusted".to_string());
+        config.client_keys.insert(
+            "trusted".to_string(),
+            public_key_to_hex(&key.verifying_key()),
+        );
+        config.client_keys.insert(
+            "signed".to_string(),
+            public_key_to_hex(&key.verifying_key()),
+        );
+        let app = ServerApp::new(config).unwrap();
+        let peers: Vec<IpAddr> = (1..=4)
+            .map(|host| format!("192.0.2.{}", host).parse().unwrap())
+            .collect();
+
+        // A forged allow-listed id is still limited by its peer address.
+        let response = app.process(&request("trusted", 3, 5), Some(peers[0])).await;
+        assert!(matches!(
+            response,
+            VerificationResponse::Unauthorized { .. }
+        ));
+        let response = app.process(&request("trusted", 3, 5), Some(peers[0])).await;
+        assert!(matches!(response, VerificationResponse::RateLimited { .. }));
+
+        // Forged requests for a registered client do not use up its bucket.
+        let response = app.process(&request("signed", 3, 5), Some(peers[1])).await;
+        assert!(matches!(
+            response,
+            VerificationResponse::Unauthorized { .. }
+        ));
+        let mut signed = request("signed", 3, 5);
+        signed.sign(&key);
+        let response = app.process(&signed, Some(peers[2])).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+        let mut signed = request("signed", 2, 7);
+        signed.sign(&key);
+        let response = app.process(&signed, Some(peers[3])).await;
+        assert!(matches!(response, VerificationResponse::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn stale_nonces_are_not_strikes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.rate_limit.ban_after_invalid = Some(1);
+        let app = ServerApp::new(config).unwrap();
+        let peer = Some("192.0.2.1".parse().unwrap());
+
+        let stale = request("client", 3, 5).with_nonce("expired");
+        let response = app.process(&stale, peer).await;
+        assert!(matches!(response, VerificationResponse::StaleNonce {}));
+        let response = app.process(&request("client", 3, 5), peer).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+    }
+
+    #[tokio::test]
+    async fn nonce_endpoint_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.max_outstanding_nonces = 1;
+        let handle = ServerApp::new(config).unwrap().start().unwrap();
+        let url = format!("http://{}{}", handle.local_addr(), NONCE_PATH);
+
+        let first = reqwest::get(&url).await.unwrap();
+        assert_eq!(first.status(), reqwest::StatusCode::OK);
+        let second = reqwest::get(&url).await.unwrap();
+        assert_eq!(second.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(second.headers().contains_key(header::RETRY_AFTER.as_str()));
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn one_peer_cannot_drain_the_nonce_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.max_outstanding_nonces = 4;
+        config.max_nonces_per_peer = 2;
+        let service = ServerApp::new(config).unwrap().into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+        let nonce_from = |peer: &str| {
+            TestRequest::get()
+                .uri(NONCE_PATH)
+                .peer_addr(peer.parse().unwrap())
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            let response = call_service(&app, nonce_from("192.0.2.1:1000")).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = call_service(&app, nonce_from("192.0.2.1:1001")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response = call_service(&app, nonce_from("192.0.2.2:1000")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    const ADMIN_TOKEN: &str = "admin-secret";
+
+    fn admin_request(request: TestRequest, path: &str) -> TestRequest {
+        request
+            .uri(&format!("{}{}", ADMIN_PATH, path))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN)))
+    }
+
+    fn verify_request(request: &ProofRequest) -> TestRequest {
+        TestRequest::post().uri(VERIFY_PATH).set_json(request)
+    }
+
+    fn admin_config(dir: &std::path::Path) -> ServerConfig {
+        let mut config = server_config(dir);
+        config.admin_token = Some(ADMIN_TOKEN.to_string());
+        config
+    }
+
+    /// Hands out a counter that moves on with every rotation.
+    struct RotatingChallenge(std::sync::atomic::AtomicU64);
+
+    impl ChallengeProvider for RotatingChallenge {
+        type Challenge = u64;
+
+        fn issue(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+
+        fn rotate(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn validate(&self, _public_inputs: &[Fr]) -> bool {
+            true
+        }
+    }
+
+    #[actix_web::test]
+    async fn admin_routes_are_not_served_without_a_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(server_config(dir.path()))
+            .unwrap()
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+
+        for (request, path) in [
+            (TestRequest::get(), "/status"),
+            (TestRequest::post(), "/pause"),
+            (TestRequest::post(), "/reload"),
+        ] {
+            let response = call_service(&app, admin_request(request, path).to_request()).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn admin_routes_require_the_bearer_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(admin_config(dir.path()))
+            .unwrap()
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+        let status = format!("{}/status", ADMIN_PATH);
+
+        let missing = TestRequest::get().uri(&status).to_request();
+        let wrong = TestRequest::get()
+            .uri(&status)
+            .insert_header((header::AUTHORIZATION, "Bearer wrong"))
+            .to_request();
+        let not_bearer = TestRequest::get()
+            .uri(&status)
+            .insert_header((header::AUTHORIZATION, ADMIN_TOKEN))
+            .to_request();
+        for request in [missing, wrong, not_bearer] {
+            let response = call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+        }
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::post(), "/pause").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(service.server().paused.load(Ordering::Relaxed));
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::get(), "/status").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let status: AdminStatus = read_body_json(response).await;
+        assert!(status.paused);
+    }
+
+    #[actix_web::test]
+    async fn paused_server_asks_clients_to_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(admin_config(dir.path()))
+            .unwrap()
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+
+        call_service(
+            &app,
+            admin_request(TestRequest::post(), "/pause").to_request(),
+        )
+        .await;
+        let response =
+            call_service(&app, verify_request(&request("client", 3, 5)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        let body: VerificationResponse = read_body_json(response).await;
+        assert!(matches!(body, VerificationResponse::Retry { .. }));
+
+        call_service(
+            &app,
+            admin_request(TestRequest::post(), "/resume").to_request(),
+        )
+        .await;
+        let response =
+            call_service(&app, verify_request(&request("client", 3, 5)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn admin_bans_revokes_and_unbans_clients() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(admin_config(dir.path()))
+            .unwrap()
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::post(), "/clients/client/ban?duration_secs=60").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response =
+            call_service(&app, verify_request(&request("client", 3, 5)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let retry_after = response.headers().get(header::RETRY_AFTER).unwrap();
+        let retry_after = retry_after.to_str().unwrap();
+        assert!(retry_after.parse::<u64>().unwrap() <= 60);
+
+        let unban = || admin_request(TestRequest::post(), "/clients/client/unban").to_request();
+        assert_eq!(
+            call_service(&app, unban()).await.status(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            call_service(&app, unban()).await.status(),
+            StatusCode::NOT_FOUND
+        );
+        let response =
+            call_service(&app, verify_request(&request("client", 3, 5)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::post(), "/clients/client/revoke").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response =
+            call_service(&app, verify_request(&request("client", 2, 7)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::get(), "/status").to_request(),
+        )
+        .await;
+        let status: AdminStatus = read_body_json(response).await;
+        assert_eq!(status.revoked_clients, ["client"]);
+
+        assert_eq!(
+            call_service(&app, unban()).await.status(),
+            StatusCode::NO_CONTENT
+        );
+        let response =
+            call_service(&app, verify_request(&request("client", 2, 7)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn admin_rotates_challenges() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(admin_config(dir.path()))
+            .unwrap()
+            .with_challenge_provider(RotatingChallenge(std::sync::atomic::AtomicU64::new(7)))
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+        let challenge = || TestRequest::get().uri(CHALLENGE_PATH).to_request();
+
+        let current: u64 = read_body_json(call_service(&app, challenge()).await).await;
+        assert_eq!(current, 7);
+        let response = call_service(
+            &app,
+            admin_request(
+                TestRequest::post(),
+                &format!("/challenges/{}/rotate", DEFAULT_CIRCUIT_ID),
+            )
+            .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let current: u64 = read_body_json(call_service(&app, challenge()).await).await;
+        assert_eq!(current, 8);
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::post(), "/challenges/unknown/rotate").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn admin_lists_clients_and_filters_submissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = admin_config(dir.path());
+        config.ledger_path = Some(dir.path().join("ledger.jsonl"));
+        let service = ServerApp::new(config).unwrap().into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+
+        for request in [
+            request("a", 3, 5),
+            request("b", 2, 7),
+            invalid_request("a"),
+            request("a", 2, 9),
+        ] {
+            call_service(&app, verify_request(&request).to_request()).await;
+        }
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::get(), "/submissions?client_id=a&limit=2").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let entries: Vec<LedgerEntry> = read_body_json(response).await;
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.client_id == "a"));
+        assert!(matches!(
+            entries[0].verdict,
+            VerificationResponse::Invalid { .. }
+        ));
+        assert_eq!(entries[1].public_inputs, ["18"]);
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::get(), "/submissions?since=99999999999999").to_request(),
+        )
+        .await;
+        let entries: Vec<LedgerEntry> = read_body_json(response).await;
+        assert!(entries.is_empty());
+
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::get(), "/clients").to_request(),
+        )
+        .await;
+        let clients: Vec<ClientSummary> = read_body_json(response).await;
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].client_id, UNAUTHENTICATED_LABEL);
+        assert_eq!((clients[0].valid, clients[0].invalid), (3, 1));
+    }
+
+    #[actix_web::test]
+    async fn submissions_need_a_ledger() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ServerApp::new(admin_config(dir.path()))
+            .unwrap()
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+        let response = call_service(
+            &app,
+            admin_request(TestRequest::get(), "/submissions").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn async_handlers_finish_before_the_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let handled = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&handled);
+        let service = ServerApp::new(server_config(dir.path()))
+            .unwrap()
+            .with_async_valid_proof_handler(move |_, _| {
+                let flag = Arc::clone(&flag);
+                async move {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    flag.store(true, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+
+        let response =
+            call_service(&app, verify_request(&request("client", 3, 5)).to_request()).await;
+        assert!(handled.load(Ordering::SeqCst));
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Rejects proofs of 15 and asks for every other proof to be sent again later.
+    struct VerdictHandler;
+
+    impl ProofHandler for VerdictHandler {
+        fn on_valid<'a>(
+            &'a self,
+            _client_id: &'a str,
+            public_inputs: &'a [Fr],
+        ) -> HandlerFuture<'a, Verdict> {
+            let verdict = if public_inputs == [Fr::from(15u64)] {
+                Verdict::Reject {
+                    reason: "Fifteen is taken".to_string(),
+                }
+            } else {
+                Verdict::Retry
+            };
+            Box::pin(async { Ok(verdict) })
+        }
+    }
+
+    #[actix_web::test]
+    async fn handler_verdicts_map_to_http_statuses() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.verify_retry_after_secs = 7;
+        let service = ServerApp::new(config)
+            .unwrap()
+            .with_handler(VerdictHandler)
+            .into_service();
+        let app = init_service(App::new().configure(|cfg| service.configure(cfg))).await;
+
+        let response =
+            call_service(&app, verify_request(&request("client", 3, 5)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: VerificationResponse = read_body_json(response).await;
+        assert!(
+            matches!(&body, VerificationResponse::Rejected { reason, .. } if reason == "Fifteen is taken"),
+            "{:?}",
+            body
+        );
+
+        let response =
+            call_service(&app, verify_request(&request("client", 2, 7)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "7");
+        let body: VerificationResponse = read_body_json(response).await;
+        assert!(
+            matches!(
+                body,
+                VerificationResponse::Retry {
+                    retry_after: Some(7)
+                }
+            ),
+            "{:?}",
+            body
+        );
+    }
+
+    type Events = Arc<std::sync::Mutex<Vec<&'static str>>>;
+
+    /// Holds every valid proof for a while and records when it runs.
+    struct SlowHandler {
+        events: Events,
+        started: Arc<tokio::sync::Notify>,
+    }
+
+    impl ProofHandler for SlowHandler {
+        fn on_valid<'a>(
+            &'a self,
+            _client_id: &'a str,
+            _public_inputs: &'a [Fr],
+        ) -> HandlerFuture<'a, Verdict> {
+            Box::pin(async move {
+                self.events.lock().unwrap().push("verifying");
+                self.started.notify_one();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                self.events.lock().unwrap().push("verified");
+                Ok(Verdict::Accept { result: None })
+            })
+        }
+
+        fn on_shutdown(&self) -> HandlerFuture<'_> {
+            self.events.lock().unwrap().push("shutdown");
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    struct EventLedger(Events);
+
+    impl Ledger for EventLedger {
+        fn record(&self, _entry: &LedgerEntry) -> Result<(), anyhow::Error> {
+            self.0.lock().unwrap().push("recorded");
+            Ok(())
+        }
+
+        fn query(&self, _query: &LedgerQuery) -> Result<Vec<LedgerEntry>, anyhow::Error> {
+            Ok(Vec::new())
+        }
+
+        fn flush(&self) -> Result<(), anyhow::Error> {
+            self.0.lock().unwrap().push("flushed");
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn graceful_stop_waits_for_in_flight_verifications() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = Events::default();
+        let started = Arc::new(tokio::sync::Notify::new());
+        let handle = ServerApp::new(server_config(dir.path()))
+            .unwrap()
+            .with_handler(SlowHandler {
+                events: Arc::clone(&events),
+                started: Arc::clone(&started),
+            })
+            .with_ledger(EventLedger(Arc::clone(&events)))
+            .start()
+            .unwrap();
+
+        let url = format!("http://{}{}", handle.local_addr(), VERIFY_PATH);
+        let submission = tokio::spawn(async move {
+            reqwest::Client::new()
+                .post(url)
+                .json(&request("client", 3, 5))
+                .send()
+                .await
+                .unwrap()
+                .json::<VerificationResponse>()
+                .await
+                .unwrap()
+        });
+        started.notified().await;
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+        let response = submission.await.unwrap();
+        assert!(
+            matches!(response, VerificationResponse::Valid { .. }),
+            "{:?}",
+            response
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["verifying", "verified", "recorded", "flushed", "shutdown"]
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_only_label_authenticated_clients() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = generate_signing_key();
+        let mut config = server_config(dir.path());
+        config.metrics_enabled = true;
+        config.client_keys.insert(
+            "signed".to_string(),
+            public_key_to_hex(&key.verifying_key()),
+        );
+        let handle = ServerApp::new(config).unwrap().start().unwrap();
+
+        let mut signed = request("signed", 3, 5);
+        signed.sign(&key);
+        let response: VerificationResponse = post(&handle, VERIFY_PATH, &signed).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+        let response: VerificationResponse =
+            post(&handle, VERIFY_PATH, &request("signed", 2, 7)).await;
+        assert!(matches!(
+            response,
+            VerificationResponse::Unauthorized { .. }
+        ));
+        let response: VerificationResponse =
+            post(&handle, VERIFY_PATH, &request("made-up-1", 2, 9)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+        let mut unknown = request("made-up-2", 2, 11);
+        unknown.circuit_id = "made-up-circuit".to_string();
+        let response: VerificationResponse = post(&handle, VERIFY_PATH, &unknown).await;
+        assert!(matches!(response, VerificationResponse::Invalid { .. }));
+
+        let metrics = get_text(&handle, METRICS_PATH).await;
+        assert!(metrics.contains(r#"client_id="signed""#), "{}", metrics);
+        assert!(
+            metrics.contains(&format!(r#"client_id="{}""#, UNAUTHENTICATED_LABEL)),
+            "{}",
+            metrics
+        );
+        assert!(
+            metrics.contains(&format!(r#"circuit_id="{}""#, UNKNOWN_CIRCUIT_LABEL)),
+            "{}",
+            metrics
+        );
+        assert!(!metrics.contains("made-up"), "{}", metrics);
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn chunked_requests_count_towards_the_request_size() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.metrics_enabled = true;
+        let handle = ServerApp::new(config).unwrap().start().unwrap();
+
+        // Sent by hand, because a chunked body has no Content-Length to read the size from.
+        let body = serde_json::to_vec(&request("client", 3, 5)).unwrap();
+        let (first, second) = body.split_at(body.len() / 2);
+        let mut raw = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+            VERIFY_PATH
+        )
+        .into_bytes();
+        for chunk in [first, second] {
+            raw.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            raw.extend_from_slice(chunk);
+            raw.extend_from_slice(b"\r\n");
+        }
+        raw.extend_from_slice(b"0\r\n\r\n");
+
+        let mut stream = tokio::net::TcpStream::connect(handle.local_addr())
+            .await
+            .unwrap();
+        stream.write_all(&raw).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        let metrics = get_text(&handle, METRICS_PATH).await;
+        let sum = format!(
+            r#"zkvc_request_size_bytes_sum{{endpoint="{}"}} {}"#,
+            VERIFY_PATH,
+            body.len()
+        );
+        assert!(metrics.contains(&sum), "{}", metrics);
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn claimed_client_labels_are_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.metrics_enabled = true;
+        config.metrics_claimed_client_labels = 2;
+        let app = ServerApp::new(config).unwrap();
+
+        for (index, client_id) in ["first", "second", "third", "first"].iter().enumerate() {
+            let response = app
+                .process(&request(client_id, 3, index as u64 + 5), None)
+                .await;
+            assert!(matches!(response, VerificationResponse::Valid { .. }));
+        }
+
+        let metrics = app.metrics.as_ref().unwrap().encode().unwrap();
+        let count = |client_id: &str| {
+            metrics
+                .lines()
+                .find(|line| line.contains(&format!(r#"client_id="{}""#, client_id)))
+                .and_then(|line| line.rsplit(' ').next())
+                .map(|count| count.to_string())
+        };
+        assert_eq!(count("first").as_deref(), Some("2"), "{}", metrics);
+        assert_eq!(count("second").as_deref(), Some("1"), "{}", metrics);
+        assert_eq!(count("third"), None, "{}", metrics);
+        assert_eq!(
+            count(UNAUTHENTICATED_LABEL).as_deref(),
+            Some("1"),
+            "{}",
+            metrics
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_requests_report_their_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.max_request_bytes = 4096;
+        config.max_batch_size = 1;
+        let handle = ServerApp::new(config).unwrap().start().unwrap();
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}{}", handle.local_addr(), path);
+
+        let mut wrong_count = request("client", 3, 5);
+        wrong_count.public_inputs.push("1".to_string());
+        let cases = [
+            (
+                VERIFY_PATH,
+                serde_json::to_vec(&wrong_count).unwrap(),
+                MalformedReason::InputCount,
+                reqwest::StatusCode::BAD_REQUEST,
+            ),
+            (
+                VERIFY_PATH,
+                b"{not json".to_vec(),
+                MalformedReason::InvalidJson,
+                reqwest::StatusCode::BAD_REQUEST,
+            ),
+            (
+                VERIFY_PATH,
+                vec![b' '; 8192],
+                MalformedReason::PayloadTooLarge,
+                reqwest::StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            (
+                VERIFY_BATCH_PATH,
+                serde_json::to_vec(&[request("client", 3, 5), request("client", 2, 7)]).unwrap(),
+                MalformedReason::PayloadTooLarge,
+                reqwest::StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+        ];
+        for (path, body, expected, status) in cases {
+            let response = client
+                .post(url(path))
+                .header(header::CONTENT_TYPE.as_str(), "application/json")
+                .body(body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{:?}", expected);
+            match response.json().await.unwrap() {
+                VerificationResponse::Malformed { reason, .. } => assert_eq!(reason, expected),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn admin_clients_only_list_authenticated_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = generate_signing_key();
+        let mut config = server_config(dir.path());
+        config.client_keys.insert(
+            "signed".to_string(),
+            public_key_to_hex(&key.verifying_key()),
+        );
+        let app = ServerApp::new(config).unwrap();
+
+        let mut signed = request("signed", 3, 5);
+        signed.sign(&key);
+        app.process(&signed, None).await;
+        app.process(&request("signed", 2, 7), None).await;
+        app.process(&request("made-up", 2, 9), None).await;
+
+        let summaries = app.clients.summaries();
+        let ids: Vec<_> = summaries.iter().map(|s| s.client_id.as_str()).collect();
+        assert_eq!(ids, [UNAUTHENTICATED_LABEL, "signed"]);
+        assert_eq!(summaries[0].valid, 1);
+    }
+
+    #[test]
+    fn reads_legacy_verification_key_path() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"listen_address": "127.0.0.1:65432", "verification_key_path": "vk.bin"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.verification_keys,
+            HashMap::from([(DEFAULT_CIRCUIT_ID.to_string(), PathBuf::from("vk.bin"))])
+        );
+    }
+
+    #[test]
+    fn reads_verification_keys_by_circuit() {
+        let config =
+            ServerConfig::new("127.0.0.1:65432", "fvk.bin").with_circuit("matrix", "mvk.bin");
+        let json = serde_json::to_string(&config).unwrap();
+        let read: ServerConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.verification_keys, config.verification_keys);
+    }
+
+    #[test]
+    fn refuses_both_key_fields() {
+        let result = serde_json::from_str::<ServerConfig>(
+            r#"{"verification_key_path": "vk.bin", "verification_keys": {"default": "vk.bin"}}"#,
+        );
+        assert!(result.unwrap_err().to_string().contains("duplicate field"));
+    }
 }
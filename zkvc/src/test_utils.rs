@@ -0,0 +1,97 @@
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_groth16::{create_random_proof, Proof, ProvingKey, VerifyingKey};
+use ark_r1cs_std::eq::EqGadget;
+use ark_relations::r1cs::SynthesisError;
+use ark_serialize::CanonicalSerialize;
+use rand::thread_rng;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::{
+    circuit::{ConstraintGenerator, ProofRequest, ZkCircuit, ZkCircuitContext, DEFAULT_CIRCUIT_ID},
+    client::ClientConfig,
+    server::ServerConfig,
+    setup::generate_keys,
+    verifier::VerifierConfig,
+};
+
+/// Proves knowledge of two factors of the public product.
+pub(crate) struct ProductCircuit {
+    pub(crate) a: u64,
+    pub(crate) b: u64,
+}
+
+impl ConstraintGenerator<Fr> for ProductCircuit {
+    fn generate_constraints(
+        &self,
+        context: &mut ZkCircuitContext<Fr>,
+    ) -> Result<(), SynthesisError> {
+        let a = context.new_witness(|| Ok(Fr::from(self.a)))?;
+        let b = context.new_witness(|| Ok(Fr::from(self.b)))?;
+        let product = context.new_public_input(|| Ok(Fr::from(self.a) * Fr::from(self.b)))?;
+        (a * b).enforce_equal(&product)
+    }
+}
+
+pub(crate) type Keys = (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>);
+
+fn setup() -> Keys {
+    generate_keys(Box::new(ProductCircuit { a: 1, b: 1 })).unwrap()
+}
+
+/// Keys for `ProductCircuit`, generated once per test run.
+pub(crate) fn keys() -> &'static Keys {
+    static KEYS: OnceLock<Keys> = OnceLock::new();
+    KEYS.get_or_init(setup)
+}
+
+pub(crate) fn prove_with(
+    proving_key: &ProvingKey<Bls12_381>,
+    a: u64,
+    b: u64,
+) -> (Proof<Bls12_381>, Vec<Fr>) {
+    let public_inputs = Arc::new(Mutex::new(Vec::new()));
+    let circuit = ZkCircuit {
+        generator: Box::new(ProductCircuit { a, b }),
+        public_inputs: Arc::clone(&public_inputs),
+    };
+    let proof = create_random_proof(circuit, proving_key, &mut thread_rng()).unwrap();
+    let public_inputs = std::mem::take(&mut *public_inputs.lock().unwrap());
+    (proof, public_inputs)
+}
+
+/// A request for the default circuit proving `a * b` with `keys()`.
+pub(crate) fn request(client_id: &str, a: u64, b: u64) -> ProofRequest {
+    let (proof, public_inputs) = prove_with(&keys().0, a, b);
+    ProofRequest::from_proof(client_id, DEFAULT_CIRCUIT_ID, &proof, &public_inputs).unwrap()
+}
+
+pub(crate) fn write_key(path: &Path, key: &impl CanonicalSerialize) -> PathBuf {
+    let mut file = std::fs::File::create(path).unwrap();
+    key.serialize_unchecked(&mut file).unwrap();
+    path.to_path_buf()
+}
+
+/// A config for the default circuit listening on a free local port, with the verifying key of
+/// `keys()` written to `dir`.
+pub(crate) fn server_config(dir: &Path) -> ServerConfig {
+    let vk_path = write_key(&dir.join("vk.bin"), &keys().1);
+    ServerConfig::new("127.0.0.1:0", vk_path)
+}
+
+/// A verifier config for the default circuit, with the verifying key of `keys()` written to `dir`.
+pub(crate) fn verifier_config(dir: &Path) -> VerifierConfig {
+    let vk_path = write_key(&dir.join("vk.bin"), &keys().1);
+    VerifierConfig::new(vk_path)
+}
+
+/// A config for `client_id` submitting to `server`, with the proving key of `keys()` written to
+/// `dir`.
+pub(crate) fn client_config(dir: &Path, server: SocketAddr, client_id: &str) -> ClientConfig {
+    let pk_path = write_key(&dir.join("pk.bin"), &keys().0);
+    let url = format!("http://{}", server).parse().unwrap();
+    ClientConfig::new(url, pk_path, client_id)
+}
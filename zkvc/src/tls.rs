@@ -0,0 +1,138 @@
+use rustls::{
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented by the server.
+    pub cert_path: PathBuf,
+    /// PEM private key for `cert_path`.
+    pub key_path: PathBuf,
+    /// PEM bundle of CAs trusted to issue client certificates. When set, every client must present
+    /// a certificate signed by one of them.
+    #[serde(default)]
+    pub client_ca_path: Option<PathBuf>,
+}
+
+pub(crate) fn server_config(config: &TlsConfig) -> Result<rustls::ServerConfig, anyhow::Error> {
+    let provider = Arc::new(ring::default_provider());
+    let certs = load_certs(&config.cert_path)?;
+    let key = load_private_key(&config.key_path)?;
+
+    let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+    let builder = match &config.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots.add(cert)?;
+            }
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(builder.with_single_cert(certs, key)?)
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, anyhow::Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No certificates found in {}",
+            path.display()
+        ));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, anyhow::Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::{ClientApp, ClientConfig},
+        server::ServerApp,
+        test_utils::server_config,
+    };
+    use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use url::Url;
+
+    /// Writes a self-signed CA and a server and a client certificate issued by it to `dir`.
+    fn write_certs(dir: &Path) {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let server = CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+        std::fs::write(dir.join("server.pem"), server.pem()).unwrap();
+        std::fs::write(dir.join("server.key"), server_key.serialize_pem()).unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+        std::fs::write(dir.join("client-tls.pem"), client.pem()).unwrap();
+        std::fs::write(dir.join("client-tls.key"), client_key.serialize_pem()).unwrap();
+    }
+
+    fn client(dir: &Path, server_url: Url, with_cert: bool) -> ClientApp {
+        let mut config = ClientConfig::new(server_url, dir.join("pk.bin"), "client");
+        config.ca_cert_path = Some(dir.join("ca.pem"));
+        if with_cert {
+            config.client_cert_path = Some(dir.join("client-tls.pem"));
+            config.client_key_path = Some(dir.join("client-tls.key"));
+        }
+        ClientApp::new(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_requires_a_client_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        write_certs(dir.path());
+        let mut config = server_config(dir.path());
+        config.tls = Some(TlsConfig {
+            cert_path: dir.path().join("server.pem"),
+            key_path: dir.path().join("server.key"),
+            client_ca_path: Some(dir.path().join("ca.pem")),
+        });
+        let handle = ServerApp::new(config).unwrap().start().unwrap();
+        let url = Url::parse(&format!("https://127.0.0.1:{}", handle.local_addr().port())).unwrap();
+
+        let info = client(dir.path(), url.clone(), true)
+            .fetch_server_info()
+            .await
+            .unwrap();
+        assert!(!info.circuits.is_empty());
+        assert!(client(dir.path(), url, false)
+            .fetch_server_info()
+            .await
+            .is_err());
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+}
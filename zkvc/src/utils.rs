@@ -1,16 +1,77 @@
-use ark_ff::{BigInteger, PrimeField};
+use ark_bls12_381::Bls12_381;
+use ark_ff::{BigInteger, FpParameters, PrimeField};
+use ark_groth16::VerifyingKey;
+use ark_serialize::CanonicalSerialize;
 use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
 
 pub(crate) const VERIFY_PATH: &str = "/verify";
+pub(crate) const VERIFY_BATCH_PATH: &str = "/verify/batch";
+pub(crate) const CHALLENGE_PATH: &str = "/challenge";
+pub(crate) const NONCE_PATH: &str = "/nonce";
+pub(crate) const METRICS_PATH: &str = "/metrics";
+pub(crate) const INFO_PATH: &str = "/info";
+pub(crate) const ADMIN_PATH: &str = "/admin";
+
+/// Compares two secrets in time that depends only on their lengths.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
 
 pub fn field_to_string<F: PrimeField>(f: F) -> String {
     let big_int = BigUint::from_bytes_le(&f.into_repr().to_bytes_le());
     big_int.to_string()
 }
 
+/// Parses a decimal string into a field element. Leading zeros are accepted, so `"007"` parses as
+/// 7; use `canonical_field_from_string` to accept only what `field_to_string` produces.
 pub fn field_from_string<F: PrimeField>(s: &str) -> Result<F, anyhow::Error> {
     let big_int = BigUint::parse_bytes(s.as_bytes(), 10)
         .ok_or_else(|| anyhow::anyhow!("Failed to parse decimal string"))?;
     let bytes = big_int.to_bytes_le();
     F::from_random_bytes(&bytes).ok_or_else(|| anyhow::anyhow!("Failed to parse field element"))
 }
+
+/// Parses the canonical decimal encoding produced by `field_to_string`: digits only, no leading
+/// zeros and a value below the field modulus.
+pub fn canonical_field_from_string<F: PrimeField>(s: &str) -> Result<F, anyhow::Error> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(anyhow::anyhow!("Failed to parse decimal string"));
+    }
+    if s.len() > 1 && s.starts_with('0') {
+        return Err(anyhow::anyhow!("Decimal string has leading zeros"));
+    }
+
+    let big_int = BigUint::parse_bytes(s.as_bytes(), 10)
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse decimal string"))?;
+    let modulus = BigUint::from_bytes_le(&F::Params::MODULUS.to_bytes_le());
+    if big_int >= modulus {
+        return Err(anyhow::anyhow!("Value is not below the field modulus"));
+    }
+    Ok(F::from_le_bytes_mod_order(&big_int.to_bytes_le()))
+}
+
+/// Hex-encoded SHA-256 of the compressed verifying key.
+pub fn key_fingerprint(verifying_key: &VerifyingKey<Bls12_381>) -> String {
+    let mut bytes = Vec::with_capacity(verifying_key.serialized_size());
+    verifying_key
+        .serialize(&mut bytes)
+        .expect("serializing a verifying key into a Vec cannot fail");
+    hex::encode(Sha256::digest(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::Fr;
+
+    #[test]
+    fn only_the_canonical_parser_refuses_leading_zeros() {
+        assert_eq!(field_from_string::<Fr>("007").unwrap(), Fr::from(7u64));
+        assert!(canonical_field_from_string::<Fr>("007").is_err());
+        assert_eq!(
+            canonical_field_from_string::<Fr>("7").unwrap(),
+            Fr::from(7u64)
+        );
+    }
+}
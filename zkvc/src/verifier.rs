@@ -0,0 +1,1451 @@
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_groth16::{prepare_verifying_key, verify_proof, PreparedVerifyingKey, Proof, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use log::{debug, error, info, warn};
+use prometheus::Histogram;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    future::{self, Future},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    batch::verify_batch,
+    challenge::{ChallengeProvider, RegisteredChallenge},
+    circuit::{ProofRequest, DEFAULT_CIRCUIT_ID},
+    handler::{
+        ErrorAdapter, ErrorFn, InvalidFn, InvalidProofAdapter, NoopHandler, ProofHandler, ValidFn,
+        ValidProofAdapter, Verdict,
+    },
+    identity::{public_key_from_hex, verify_request},
+    metrics::Metrics,
+    policy::InputPolicy,
+    pool::{PoolError, VerificationPool},
+    replay::{submission_digest, InMemorySeenSet, NonceIssuer, SeenSet, SubmissionDigest},
+    response::{
+        CircuitInfo, MalformedReason, ServerInfo, VerificationResponse, CURVE, PROTOCOL_VERSION,
+    },
+    utils::{canonical_field_from_string, key_fingerprint},
+};
+
+struct LoadedKey {
+    prepared: Arc<PreparedVerifyingKey<Bls12_381>>,
+    fingerprint: String,
+}
+
+impl LoadedKey {
+    fn load(circuit_id: &str, path: &Path) -> Result<Self, anyhow::Error> {
+        let (vk, fingerprint) = read_key(path)?;
+        Ok(Self::prepare(circuit_id, &vk, fingerprint))
+    }
+
+    fn prepare(circuit_id: &str, vk: &VerifyingKey<Bls12_381>, fingerprint: String) -> Self {
+        let start = Instant::now();
+        let pvk = prepare_verifying_key(vk);
+        info!(
+            "Verification key for circuit {} ({}) loaded and prepared successfully in {:?}",
+            circuit_id,
+            fingerprint,
+            start.elapsed()
+        );
+        Self {
+            prepared: Arc::new(pvk),
+            fingerprint,
+        }
+    }
+
+    fn public_inputs(&self) -> usize {
+        self.prepared.vk.gamma_abc_g1.len() - 1
+    }
+}
+
+/// The current key of a circuit and, after a reload, the key it replaced until its grace period
+/// ends.
+struct CircuitKeys {
+    current: LoadedKey,
+    previous: Option<(LoadedKey, Instant)>,
+}
+
+impl CircuitKeys {
+    /// Keys that proofs may currently be checked against, newest first.
+    fn accepted(&self, now: Instant) -> impl Iterator<Item = &LoadedKey> {
+        let previous = self
+            .previous
+            .as_ref()
+            .filter(|(_, until)| *until > now)
+            .map(|(key, _)| key);
+        std::iter::once(&self.current).chain(previous)
+    }
+}
+
+#[derive(Clone)]
+struct Submission {
+    /// Every accepted key of the circuit with a matching number of public inputs, newest first.
+    verification_keys: Vec<Arc<PreparedVerifyingKey<Bls12_381>>>,
+    proof: Proof<Bls12_381>,
+    inputs: Vec<Fr>,
+    digest: SubmissionDigest,
+}
+
+/// Settings of a `Verifier`. `ServerConfig` carries the same fields next to its HTTP settings and
+/// converts into this with `VerifierConfig::from`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerifierConfig {
+    /// Verification key files by circuit id.
+    pub verification_keys: HashMap<String, PathBuf>,
+    pub replay_retention_secs: u64,
+    pub require_nonce: bool,
+    /// How long an issued nonce stays valid if no proof uses it.
+    pub nonce_ttl_secs: u64,
+    /// Nonces from `Verifier::issue_nonce` that may be outstanding at once.
+    pub max_outstanding_nonces: usize,
+    /// Nonces that may be outstanding at once for a single peer address.
+    pub max_nonces_per_peer: usize,
+    pub verify_concurrency: usize,
+    pub verify_queue_depth: usize,
+    pub verify_retry_after_secs: u64,
+    /// Hex-encoded ed25519 public keys by client id. Requests naming a registered client must be
+    /// signed with its key.
+    pub client_keys: HashMap<String, String>,
+    /// Refuse requests from clients without a registered key.
+    pub require_signatures: bool,
+    /// How long proofs for a replaced verification key are still accepted.
+    pub key_grace_secs: u64,
+}
+
+impl Default for VerifierConfig {
+    fn default() -> Self {
+        Self {
+            verification_keys: HashMap::new(),
+            replay_retention_secs: 3600,
+            require_nonce: false,
+            nonce_ttl_secs: 300,
+            max_outstanding_nonces: 100_000,
+            max_nonces_per_peer: 64,
+            verify_concurrency: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            verify_queue_depth: 64,
+            verify_retry_after_secs: 1,
+            client_keys: HashMap::new(),
+            require_signatures: false,
+            key_grace_secs: 300,
+        }
+    }
+}
+
+impl VerifierConfig {
+    pub fn new(verification_key_path: impl Into<PathBuf>) -> Self {
+        Self::default().with_circuit(DEFAULT_CIRCUIT_ID, verification_key_path)
+    }
+
+    pub fn with_circuit(
+        mut self,
+        circuit_id: impl Into<String>,
+        verification_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.verification_keys
+            .insert(circuit_id.into(), verification_key_path.into());
+        self
+    }
+}
+
+/// Decodes, validates and verifies proof requests and dispatches the outcome to the configured
+/// handlers, independently of how the requests arrive.
+///
+/// `ServerApp` wraps a `Verifier` with HTTP routes, rate limits, the ledger and metrics.
+pub struct Verifier {
+    key_paths: HashMap<String, PathBuf>,
+    verification_keys: RwLock<HashMap<String, CircuitKeys>>,
+    /// Held for a whole reload, so two reloads cannot both replace the same key.
+    reload_lock: tokio::sync::Mutex<()>,
+    key_grace: Duration,
+    handler: Box<dyn ProofHandler>,
+    circuit_handlers: HashMap<String, Box<dyn ProofHandler>>,
+    challenges: HashMap<String, RegisteredChallenge>,
+    policies: HashMap<String, InputPolicy>,
+    seen: Box<dyn SeenSet>,
+    nonces: NonceIssuer,
+    pool: VerificationPool,
+    client_keys: HashMap<String, ed25519_dalek::VerifyingKey>,
+    metrics: Option<Arc<Metrics>>,
+    replay_retention: Duration,
+    require_nonce: bool,
+    require_signatures: bool,
+    retry_after_secs: u64,
+}
+
+impl Verifier {
+    /// Loads the verification keys and client keys named in `config`.
+    pub fn new(config: &VerifierConfig) -> Result<Self, anyhow::Error> {
+        Self::with_metrics(config, None)
+    }
+
+    /// Like `new`, recording pairing-check latency and outcomes to `metrics`.
+    pub(crate) fn with_metrics(
+        config: &VerifierConfig,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Result<Self, anyhow::Error> {
+        debug!("Creating new Verifier instance");
+        if config.verification_keys.is_empty() {
+            return Err(anyhow::anyhow!("No verification keys configured"));
+        }
+
+        let verification_keys = config
+            .verification_keys
+            .iter()
+            .map(|(circuit_id, path)| {
+                let keys = CircuitKeys {
+                    current: LoadedKey::load(circuit_id, path)?,
+                    previous: None,
+                };
+                Ok((circuit_id.clone(), keys))
+            })
+            .collect::<Result<HashMap<_, _>, anyhow::Error>>()?;
+
+        let client_keys = config
+            .client_keys
+            .iter()
+            .map(|(client_id, key)| {
+                let key = public_key_from_hex(key).map_err(|e| {
+                    anyhow::anyhow!("Invalid public key for client {}: {}", client_id, e)
+                })?;
+                Ok((client_id.clone(), key))
+            })
+            .collect::<Result<HashMap<_, _>, anyhow::Error>>()?;
+
+        let replay_retention = Duration::from_secs(config.replay_retention_secs);
+        Ok(Self {
+            key_paths: config.verification_keys.clone(),
+            verification_keys: RwLock::new(verification_keys),
+            reload_lock: tokio::sync::Mutex::new(()),
+            key_grace: Duration::from_secs(config.key_grace_secs),
+            handler: Box::new(NoopHandler),
+            circuit_handlers: HashMap::new(),
+            challenges: HashMap::new(),
+            policies: HashMap::new(),
+            seen: Box::new(InMemorySeenSet::new()),
+            nonces: NonceIssuer::new(
+                Duration::from_secs(config.nonce_ttl_secs),
+                config.max_outstanding_nonces,
+                config.max_nonces_per_peer,
+            ),
+            pool: VerificationPool::new(config.verify_concurrency, config.verify_queue_depth),
+            client_keys,
+            metrics,
+            replay_retention,
+            require_nonce: config.require_nonce,
+            require_signatures: config.require_signatures,
+            retry_after_secs: config.verify_retry_after_secs,
+        })
+    }
+
+    /// Sets the handler of every circuit without its own. This replaces closures registered
+    /// earlier with the `with_*_handler` builders; closures registered afterwards override the
+    /// matching method of `handler` and keep the others.
+    pub fn with_handler(mut self, handler: impl ProofHandler) -> Self {
+        self.handler = Box::new(handler);
+        self
+    }
+
+    pub fn with_circuit_handler(
+        mut self,
+        circuit_id: impl Into<String>,
+        handler: impl ProofHandler,
+    ) -> Self {
+        self.circuit_handlers
+            .insert(circuit_id.into(), Box::new(handler));
+        self
+    }
+
+    pub fn with_valid_proof_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(&str, &[Fr]) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        self.wrap_valid(Box::new(move |client_id, inputs| {
+            Box::pin(future::ready(
+                f(client_id, inputs).map(|()| Verdict::Accept { result: None }),
+            ))
+        }))
+    }
+
+    pub fn with_invalid_proof_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(&str, &str) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        self.wrap_invalid(Box::new(move |client_id, reason| {
+            Box::pin(future::ready(f(client_id, reason)))
+        }))
+    }
+
+    pub fn with_error_handler<F>(self, f: F) -> Self
+    where
+        F: Fn(&str, &anyhow::Error) -> Result<(), anyhow::Error> + Send + Sync + 'static,
+    {
+        self.wrap_error(Box::new(move |client_id, error| {
+            Box::pin(future::ready(f(client_id, error)))
+        }))
+    }
+
+    pub fn with_async_valid_proof_handler<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(String, Vec<Fr>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.wrap_valid(Box::new(move |client_id, inputs| {
+            let handled = f(client_id.to_string(), inputs.to_vec());
+            Box::pin(async move {
+                handled.await?;
+                Ok(Verdict::Accept { result: None })
+            })
+        }))
+    }
+
+    pub fn with_async_invalid_proof_handler<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(String, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.wrap_invalid(Box::new(move |client_id, reason| {
+            Box::pin(f(client_id.to_string(), reason.to_string()))
+        }))
+    }
+
+    pub fn with_async_error_handler<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(String, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.wrap_error(Box::new(move |client_id, error| {
+            Box::pin(f(client_id.to_string(), format!("{:#}", error)))
+        }))
+    }
+
+    fn wrap_valid(mut self, f: ValidFn) -> Self {
+        self.handler = Box::new(ValidProofAdapter {
+            inner: self.handler,
+            f,
+        });
+        self
+    }
+
+    fn wrap_invalid(mut self, f: InvalidFn) -> Self {
+        self.handler = Box::new(InvalidProofAdapter {
+            inner: self.handler,
+            f,
+        });
+        self
+    }
+
+    fn wrap_error(mut self, f: ErrorFn) -> Self {
+        self.handler = Box::new(ErrorAdapter {
+            inner: self.handler,
+            f,
+        });
+        self
+    }
+
+    pub fn with_challenge_provider(self, provider: impl ChallengeProvider) -> Self {
+        self.with_circuit_challenge_provider(DEFAULT_CIRCUIT_ID, provider)
+    }
+
+    pub fn with_circuit_challenge_provider(
+        mut self,
+        circuit_id: impl Into<String>,
+        provider: impl ChallengeProvider,
+    ) -> Self {
+        self.challenges
+            .insert(circuit_id.into(), RegisteredChallenge::new(provider));
+        self
+    }
+
+    pub fn with_input_policy(self, policy: InputPolicy) -> Self {
+        self.with_circuit_input_policy(DEFAULT_CIRCUIT_ID, policy)
+    }
+
+    pub fn with_circuit_input_policy(
+        mut self,
+        circuit_id: impl Into<String>,
+        policy: InputPolicy,
+    ) -> Self {
+        self.policies.insert(circuit_id.into(), policy);
+        self
+    }
+
+    pub fn with_seen_set(mut self, seen: impl SeenSet) -> Self {
+        self.seen = Box::new(seen);
+        self
+    }
+
+    pub fn with_client_key(
+        mut self,
+        client_id: impl Into<String>,
+        key: ed25519_dalek::VerifyingKey,
+    ) -> Self {
+        self.client_keys.insert(client_id.into(), key);
+        self
+    }
+
+    /// Verifies a single request and runs the matching handler.
+    pub async fn verify(&self, request: &ProofRequest) -> VerificationResponse {
+        let submission = match self.prepare(request).await {
+            Ok(submission) => submission,
+            Err(response) => return response,
+        };
+        let job = submission.clone();
+        let pairing = self.pairing_histogram("single");
+        let result = match self
+            .pool
+            .run(move || {
+                let _timer = pairing.map(|histogram| histogram.start_timer());
+                Self::check_pairing(&job)
+            })
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return self.pool_failure(e),
+        };
+        self.conclude(request, submission, result).await
+    }
+
+    /// Verifies several requests, sharing one batched pairing check per circuit. Responses are in
+    /// the order of `requests`.
+    pub async fn verify_batch(&self, requests: &[ProofRequest]) -> Vec<VerificationResponse> {
+        let requests: Vec<&ProofRequest> = requests.iter().collect();
+        self.verify_all(&requests).await
+    }
+
+    /// Issues a nonce for `ProofRequest::with_nonce`. It stays valid for `nonce_ttl_secs` or until
+    /// a proof using it is accepted. Fails while `max_outstanding_nonces` nonces are outstanding.
+    pub fn issue_nonce(&self) -> Result<String, anyhow::Error> {
+        self.issue_nonce_for(None)
+    }
+
+    /// Like `issue_nonce`, also failing while `max_nonces_per_peer` nonces issued to `peer` are
+    /// outstanding.
+    pub fn issue_nonce_for(&self, peer: Option<IpAddr>) -> Result<String, anyhow::Error> {
+        self.nonces
+            .issue(peer)
+            .ok_or_else(|| anyhow::anyhow!("Too many outstanding nonces"))
+    }
+
+    /// The current challenge for a circuit, or `None` if it has no challenge provider.
+    pub fn issue_challenge(
+        &self,
+        circuit_id: &str,
+    ) -> Option<Result<serde_json::Value, anyhow::Error>> {
+        self.challenges
+            .get(circuit_id)
+            .map(|challenge| challenge.provider.issue_json())
+    }
+
+    pub fn get_circuit_ids(&self) -> impl Iterator<Item = &str> {
+        self.key_paths.keys().map(String::as_str)
+    }
+
+    pub(crate) fn has_circuit(&self, circuit_id: &str) -> bool {
+        self.key_paths.contains_key(circuit_id)
+    }
+
+    /// Whether `request` is signed with the registered key of the client it names.
+    pub(crate) fn is_authenticated(&self, request: &ProofRequest) -> bool {
+        self.client_keys
+            .get(&request.client_id)
+            .is_some_and(|key| verify_request(request, key).is_ok())
+    }
+
+    /// Fingerprint of the current verification key of a circuit.
+    pub fn key_fingerprint(&self, circuit_id: &str) -> Option<String> {
+        self.verification_keys
+            .read()
+            .unwrap()
+            .get(circuit_id)
+            .map(|keys| keys.current.fingerprint.clone())
+    }
+
+    pub fn info(&self) -> ServerInfo {
+        let circuits = self
+            .verification_keys
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(circuit_id, keys)| {
+                let info = CircuitInfo {
+                    key_fingerprint: keys.current.fingerprint.clone(),
+                    public_inputs: keys.current.public_inputs(),
+                };
+                (circuit_id.clone(), info)
+            })
+            .collect();
+
+        ServerInfo {
+            protocol_version: PROTOCOL_VERSION,
+            curve: CURVE.to_string(),
+            circuits,
+        }
+    }
+
+    /// Re-reads every verification key file and swaps in the keys that changed. Proofs made for a
+    /// replaced key are still accepted for `VerifierConfig::key_grace_secs`. If any file cannot be
+    /// loaded, no key is swapped.
+    ///
+    /// The files are read and prepared on the blocking thread pool, and concurrent reloads run one
+    /// after the other, so a key replaced by one of them keeps its grace period.
+    ///
+    /// Returns the ids of the circuits whose key changed.
+    pub async fn reload_keys(&self) -> Result<Vec<String>, anyhow::Error> {
+        let _reload_guard = self.reload_lock.lock().await;
+        let fingerprints: HashMap<String, String> = self
+            .verification_keys
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(circuit_id, keys)| (circuit_id.clone(), keys.current.fingerprint.clone()))
+            .collect();
+        let key_paths = self.key_paths.clone();
+        let prepared =
+            tokio::task::spawn_blocking(move || load_changed_keys(&key_paths, &fingerprints))
+                .await??;
+        if prepared.is_empty() {
+            debug!("Verification keys are unchanged");
+            return Ok(Vec::new());
+        }
+
+        let grace_until = Instant::now() + self.key_grace;
+        let mut keys = self.verification_keys.write().unwrap();
+        let mut reloaded = Vec::with_capacity(prepared.len());
+        for (circuit_id, key) in prepared {
+            let entry = keys
+                .get_mut(&circuit_id)
+                .expect("every key path has a loaded key");
+            let previous = std::mem::replace(&mut entry.current, key);
+            info!(
+                "Circuit {} now uses key {}, key {} stays accepted for {:?}",
+                circuit_id, entry.current.fingerprint, previous.fingerprint, self.key_grace
+            );
+            entry.previous = Some((previous, grace_until));
+            reloaded.push(circuit_id);
+        }
+        Ok(reloaded)
+    }
+
+    /// Reloads the verification keys and then calls `ProofHandler::on_reload` on every handler.
+    pub async fn reload(&self) -> Result<Vec<String>, anyhow::Error> {
+        let reloaded = self.reload_keys().await?;
+        self.notify_reload().await;
+        Ok(reloaded)
+    }
+
+    /// Reloads the keys every `interval`, notifying the handlers whenever one changed. Never
+    /// returns.
+    pub(crate) async fn watch_keys(&self, interval: Duration) {
+        info!(
+            "Checking verification keys for changes every {:?}",
+            interval
+        );
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match self.reload_keys().await {
+                Ok(reloaded) if !reloaded.is_empty() => self.notify_reload().await,
+                Ok(_) => {}
+                Err(e) => warn!("Keeping the current verification keys: {}", e),
+            }
+        }
+    }
+
+    async fn notify_reload(&self) {
+        let handlers = std::iter::once(&self.handler).chain(self.circuit_handlers.values());
+        for handler in handlers {
+            if let Err(e) = handler.on_reload().await {
+                error!("Reload handler failed: {}", e);
+            }
+        }
+    }
+
+    /// Moves a circuit's challenge on without waiting for an accepted proof. Returns `false` if
+    /// the circuit has no challenge provider.
+    pub async fn rotate_challenge(&self, circuit_id: &str) -> bool {
+        let Some(challenge) = self.challenges.get(circuit_id) else {
+            return false;
+        };
+        let _consume_guard = challenge.consume_lock.lock().await;
+        challenge.provider.rotate();
+        true
+    }
+
+    /// Waits for in-flight pairing checks to finish, then calls `ProofHandler::on_shutdown` on
+    /// every handler.
+    pub async fn shutdown(&self) {
+        self.drain().await;
+        self.shut_down_handlers().await;
+    }
+
+    pub(crate) async fn drain(&self) {
+        debug!("Waiting for in-flight verifications to finish");
+        self.pool.drain().await;
+    }
+
+    pub(crate) async fn shut_down_handlers(&self) {
+        let handlers = std::iter::once(&self.handler).chain(self.circuit_handlers.values());
+        for handler in handlers {
+            if let Err(e) = handler.on_shutdown().await {
+                error!("Shutdown handler failed: {}", e);
+            }
+        }
+    }
+
+    pub(crate) fn pending(&self) -> usize {
+        self.pool.pending()
+    }
+
+    fn handler_for(&self, circuit_id: &str) -> &dyn ProofHandler {
+        match self.circuit_handlers.get(circuit_id) {
+            Some(handler) => handler.as_ref(),
+            None => self.handler.as_ref(),
+        }
+    }
+
+    fn check_pairing(submission: &Submission) -> Result<bool, anyhow::Error> {
+        debug!("Verifying proof");
+        let start = Instant::now();
+
+        let mut result = false;
+        for (index, verification_key) in submission.verification_keys.iter().enumerate() {
+            if verify_proof(verification_key, &submission.proof, &submission.inputs)? {
+                if index > 0 {
+                    info!("Proof accepted under a previous verification key");
+                }
+                result = true;
+                break;
+            }
+        }
+        debug!("Proof verification completed in {:?}", start.elapsed());
+        info!("Proof verification result: {}", result);
+        Ok(result)
+    }
+
+    fn verify_group(circuit_id: &str, group: &[Submission]) -> Vec<Result<bool, anyhow::Error>> {
+        // While a replaced key is in its grace period, proofs of one circuit can be meant for
+        // different keys, so each batch only holds proofs whose first candidate key is the same.
+        let mut by_key: Vec<(&Arc<PreparedVerifyingKey<Bls12_381>>, Vec<usize>)> = Vec::new();
+        for (index, submission) in group.iter().enumerate() {
+            let Some(key) = submission.verification_keys.first() else {
+                continue;
+            };
+            match by_key.iter_mut().find(|(other, _)| Arc::ptr_eq(other, key)) {
+                Some((_, indices)) => indices.push(index),
+                None => by_key.push((key, vec![index])),
+            }
+        }
+
+        let mut results: Vec<Option<Result<bool, anyhow::Error>>> =
+            group.iter().map(|_| None).collect();
+        for (key, indices) in by_key {
+            let items: Vec<_> = indices
+                .iter()
+                .map(|&index| (&group[index].proof, group[index].inputs.as_slice()))
+                .collect();
+
+            let start = Instant::now();
+            if let Ok(true) = verify_batch(key, &items) {
+                debug!(
+                    "Batch of {} proofs for circuit {} verified in {:?}",
+                    indices.len(),
+                    circuit_id,
+                    start.elapsed()
+                );
+                for index in indices {
+                    results[index] = Some(Ok(true));
+                }
+            } else {
+                debug!(
+                    "Batch check for circuit {} failed, checking {} proofs individually",
+                    circuit_id,
+                    indices.len()
+                );
+                for index in indices {
+                    results[index] = Some(Self::check_pairing(&group[index]));
+                }
+            }
+        }
+
+        group
+            .iter()
+            .zip(results)
+            .map(|(submission, result)| result.unwrap_or_else(|| Self::check_pairing(submission)))
+            .collect()
+    }
+
+    fn pairing_histogram(&self, mode: &str) -> Option<Histogram> {
+        self.metrics
+            .as_ref()
+            .map(|metrics| metrics.pairing_seconds.with_label_values(&[mode]))
+    }
+
+    fn pool_failure(&self, error: PoolError) -> VerificationResponse {
+        match error {
+            PoolError::Full => {
+                warn!("Verification queue is full, asking client to retry later");
+                VerificationResponse::Retry {
+                    retry_after: Some(self.retry_after_secs),
+                }
+            }
+            PoolError::Failed(e) => {
+                error!("Verification task failed: {}", e);
+                VerificationResponse::Error {
+                    error: e.to_string(),
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn verify_all(&self, requests: &[&ProofRequest]) -> Vec<VerificationResponse> {
+        let mut prepared = Vec::with_capacity(requests.len());
+        for request in requests {
+            prepared.push(self.prepare(request).await);
+        }
+
+        let mut by_circuit: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, request) in requests.iter().enumerate() {
+            if prepared[index].is_ok() {
+                by_circuit
+                    .entry(request.circuit_id.clone())
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut results: Vec<Option<Result<bool, anyhow::Error>>> =
+            requests.iter().map(|_| None).collect();
+        for (circuit_id, indices) in by_circuit {
+            let group: Vec<Submission> = indices
+                .iter()
+                .filter_map(|&index| prepared[index].as_ref().ok().cloned())
+                .collect();
+            let pairing = self.pairing_histogram("batch");
+            match self
+                .pool
+                .run(move || {
+                    let _timer = pairing.map(|histogram| histogram.start_timer());
+                    Self::verify_group(&circuit_id, &group)
+                })
+                .await
+            {
+                Ok(group_results) => {
+                    for (&index, result) in indices.iter().zip(group_results) {
+                        results[index] = Some(result);
+                    }
+                }
+                Err(e) => {
+                    let response = self.pool_failure(e);
+                    for &index in &indices {
+                        prepared[index] = Err(response.clone());
+                    }
+                }
+            }
+        }
+
+        let mut responses = Vec::with_capacity(requests.len());
+        for ((request, prepared), result) in requests.iter().zip(prepared).zip(results) {
+            let response = match (prepared, result) {
+                (Err(response), _) => response,
+                (Ok(submission), Some(result)) => self.conclude(request, submission, result).await,
+                (Ok(_), None) => unreachable!("every prepared submission is verified"),
+            };
+            responses.push(response);
+        }
+        responses
+    }
+
+    async fn prepare(&self, request: &ProofRequest) -> Result<Submission, VerificationResponse> {
+        if let Err(reason) = self.authenticate(request) {
+            warn!(
+                "Refusing unauthenticated submission claiming client {}: {}",
+                request.client_id, reason
+            );
+            return Err(VerificationResponse::Unauthorized { reason });
+        }
+
+        let handler = self.handler_for(&request.circuit_id);
+
+        let candidates = self
+            .verification_keys
+            .read()
+            .unwrap()
+            .get(&request.circuit_id)
+            .map(|keys| {
+                let matching: Vec<_> = keys
+                    .accepted(Instant::now())
+                    .filter(|key| key.public_inputs() == request.public_inputs.len())
+                    .map(|key| Arc::clone(&key.prepared))
+                    .collect();
+                (matching, keys.current.public_inputs())
+            });
+        let (verification_keys, current_inputs) = match candidates {
+            Some(candidates) => candidates,
+            None => {
+                warn!(
+                    "Client {} requested unknown circuit {}",
+                    request.client_id, request.circuit_id
+                );
+                let reason = format!("Unknown circuit id: {}", request.circuit_id);
+                return Err(invalid(handler, &request.client_id, reason).await);
+            }
+        };
+
+        let start = Instant::now();
+        let expected_inputs = if verification_keys.is_empty() {
+            current_inputs
+        } else {
+            request.public_inputs.len()
+        };
+        let (proof, inputs) = match decode_request(request, expected_inputs) {
+            Ok(decoded) => decoded,
+            Err((reason, message)) => {
+                warn!(
+                    "Malformed request from client {}: {}",
+                    request.client_id, message
+                );
+                notify_invalid(handler, &request.client_id, &message).await;
+                return Err(VerificationResponse::Malformed { reason, message });
+            }
+        };
+        debug!("Request decoded in {:?}", start.elapsed());
+        if let Some(metrics) = &self.metrics {
+            metrics
+                .decode_seconds
+                .observe(start.elapsed().as_secs_f64());
+        }
+        debug!("Inputs: {:?}", inputs);
+
+        let digest = submission_digest(&request.circuit_id, &inputs);
+        if self.seen.contains(&digest) {
+            info!("Duplicate submission from client {}", request.client_id);
+            return Err(VerificationResponse::Duplicate {});
+        }
+
+        match &request.nonce {
+            Some(nonce) if !self.nonces.is_valid(nonce) => {
+                info!("Client {} used an unknown nonce", request.client_id);
+                return Err(VerificationResponse::StaleNonce {});
+            }
+            None if self.require_nonce => {
+                warn!("Client {} did not provide a nonce", request.client_id);
+                let reason = "Missing nonce".to_string();
+                return Err(invalid(handler, &request.client_id, reason).await);
+            }
+            _ => {}
+        }
+
+        Ok(Submission {
+            verification_keys,
+            proof,
+            inputs,
+            digest,
+        })
+    }
+
+    fn authenticate(&self, request: &ProofRequest) -> Result<(), String> {
+        match self.client_keys.get(&request.client_id) {
+            Some(key) => verify_request(request, key),
+            None if self.require_signatures => Err(format!("Unknown client {}", request.client_id)),
+            None => Ok(()),
+        }
+    }
+
+    async fn conclude(
+        &self,
+        request: &ProofRequest,
+        submission: Submission,
+        result: Result<bool, anyhow::Error>,
+    ) -> VerificationResponse {
+        let handler = self.handler_for(&request.circuit_id);
+        match result {
+            Ok(true) => {
+                info!(
+                    "Proof verified successfully for client {}",
+                    request.client_id
+                );
+                self.accept(handler, request, &submission.inputs, submission.digest)
+                    .await
+            }
+            Ok(false) => {
+                warn!("Invalid proof received from client {}", request.client_id);
+                let reason = "Proof verification failed".to_string();
+                invalid(handler, &request.client_id, reason).await
+            }
+            Err(e) => {
+                error!("Verification error for client {}: {}", request.client_id, e);
+                if let Err(handler_err) = handler.on_error(&request.client_id, &e).await {
+                    error!("Error handler failed: {}", handler_err);
+                }
+                VerificationResponse::Error {
+                    error: e.to_string(),
+                }
+            }
+        }
+    }
+
+    async fn accept(
+        &self,
+        handler: &dyn ProofHandler,
+        request: &ProofRequest,
+        inputs: &[Fr],
+        digest: SubmissionDigest,
+    ) -> VerificationResponse {
+        // Held from the policy check until the challenge has been rotated, so neither can see a
+        // challenge another proof has already answered.
+        let challenge = self.challenges.get(&request.circuit_id);
+        let _consume_guard = match challenge {
+            Some(challenge) => Some(challenge.consume_lock.lock().await),
+            None => None,
+        };
+
+        if let Some(policy) = self.policies.get(&request.circuit_id) {
+            let provider = challenge.map(|challenge| challenge.provider.as_ref());
+            if let Err(violation) = policy.check_with_challenge(inputs, provider) {
+                warn!(
+                    "Proof from client {} violates the input policy: {}",
+                    request.client_id, violation.message
+                );
+                notify_invalid(handler, &request.client_id, &violation.message).await;
+                return VerificationResponse::Rejected {
+                    reason: violation.message.clone(),
+                    violation: Some(violation),
+                };
+            }
+        }
+
+        if let Some(challenge) = challenge {
+            if !challenge.provider.validate(inputs) {
+                warn!(
+                    "Client {} provided a proof for a stale challenge",
+                    request.client_id
+                );
+                let reason = "Proof does not answer the current challenge".to_string();
+                notify_invalid(handler, &request.client_id, &reason).await;
+                return VerificationResponse::Rejected {
+                    reason,
+                    violation: None,
+                };
+            }
+        }
+
+        // Taken rather than checked, so two requests sharing a nonce cannot both get past here.
+        // It is handed back below unless the proof ends up accepted.
+        let nonce = match &request.nonce {
+            Some(nonce) => match self.nonces.take(nonce) {
+                Some(issued) => Some((nonce, issued)),
+                None => {
+                    info!("Client {} reused a nonce", request.client_id);
+                    return VerificationResponse::StaleNonce {};
+                }
+            },
+            None => None,
+        };
+        let restore_nonce = || {
+            if let Some((nonce, issued)) = nonce {
+                self.nonces.restore(nonce, issued);
+            }
+        };
+
+        let expires_at = SystemTime::now() + self.replay_retention;
+        if !self.seen.insert(digest, expires_at) {
+            info!("Duplicate submission from client {}", request.client_id);
+            restore_nonce();
+            return VerificationResponse::Duplicate {};
+        }
+
+        let verdict = match handler.on_valid(&request.client_id, inputs).await {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                error!("Valid proof handler failed: {}", e);
+                self.seen.remove(&digest);
+                restore_nonce();
+                return VerificationResponse::Error {
+                    error: e.to_string(),
+                };
+            }
+        };
+        if !matches!(verdict, Verdict::Accept { .. }) {
+            self.seen.remove(&digest);
+            restore_nonce();
+        }
+
+        match verdict {
+            Verdict::Accept { result } => {
+                if let Some(challenge) = challenge {
+                    debug!("Rotating challenge for circuit {}", request.circuit_id);
+                    challenge.provider.rotate();
+                }
+                VerificationResponse::Valid {
+                    result: result.or_else(|| Some(request.public_inputs.clone())),
+                }
+            }
+            Verdict::Reject { reason } => {
+                info!(
+                    "Proof from client {} rejected by handler: {}",
+                    request.client_id, reason
+                );
+                VerificationResponse::Rejected {
+                    reason,
+                    violation: None,
+                }
+            }
+            Verdict::Retry => {
+                info!("Handler asked client {} to retry later", request.client_id);
+                VerificationResponse::Retry {
+                    retry_after: Some(self.retry_after_secs),
+                }
+            }
+        }
+    }
+}
+
+/// Reads the key files of `key_paths` and prepares those whose fingerprint differs from the one
+/// in `fingerprints`.
+fn load_changed_keys(
+    key_paths: &HashMap<String, PathBuf>,
+    fingerprints: &HashMap<String, String>,
+) -> Result<Vec<(String, LoadedKey)>, anyhow::Error> {
+    let mut changed = Vec::new();
+    for (circuit_id, path) in key_paths {
+        let (vk, fingerprint) = read_key(path).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to reload the key for circuit {} from {}: {}",
+                circuit_id,
+                path.display(),
+                e
+            )
+        })?;
+        if fingerprints.get(circuit_id) != Some(&fingerprint) {
+            changed.push((circuit_id.clone(), vk, fingerprint));
+        }
+    }
+    Ok(changed
+        .into_iter()
+        .map(|(circuit_id, vk, fingerprint)| {
+            let key = LoadedKey::prepare(&circuit_id, &vk, fingerprint);
+            (circuit_id, key)
+        })
+        .collect())
+}
+
+fn read_key(path: &Path) -> Result<(VerifyingKey<Bls12_381>, String), anyhow::Error> {
+    let vk_bytes = std::fs::read(path)?;
+    let vk = VerifyingKey::deserialize_unchecked(&*vk_bytes)?;
+    let fingerprint = key_fingerprint(&vk);
+    Ok((vk, fingerprint))
+}
+
+fn decode_request(
+    request: &ProofRequest,
+    expected_inputs: usize,
+) -> Result<(Proof<Bls12_381>, Vec<Fr>), (MalformedReason, String)> {
+    if request.public_inputs.len() != expected_inputs {
+        return Err((
+            MalformedReason::InputCount,
+            format!(
+                "Expected {} public inputs, got {}",
+                expected_inputs,
+                request.public_inputs.len()
+            ),
+        ));
+    }
+
+    let proof_bytes = STANDARD.decode(&request.proof.0).map_err(|e| {
+        (
+            MalformedReason::InvalidBase64,
+            format!("Proof is not valid base64: {}", e),
+        )
+    })?;
+    let proof_length = Proof::<Bls12_381>::default().uncompressed_size();
+    if proof_bytes.len() != proof_length {
+        return Err((
+            MalformedReason::ProofLength,
+            format!(
+                "Proof is {} bytes long, expected {}",
+                proof_bytes.len(),
+                proof_length
+            ),
+        ));
+    }
+    let proof = Proof::<Bls12_381>::deserialize_uncompressed(&*proof_bytes).map_err(|e| {
+        (
+            MalformedReason::InvalidProof,
+            format!("Proof is not a valid Groth16 proof: {}", e),
+        )
+    })?;
+
+    let inputs = request
+        .public_inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            canonical_field_from_string(input).map_err(|e| {
+                (
+                    MalformedReason::NonCanonicalInput,
+                    format!(
+                        "Public input {} is not a canonical field element: {}",
+                        index, e
+                    ),
+                )
+            })
+        })
+        .collect::<Result<Vec<Fr>, _>>()?;
+    Ok((proof, inputs))
+}
+
+async fn invalid(
+    handler: &dyn ProofHandler,
+    client_id: &str,
+    reason: String,
+) -> VerificationResponse {
+    notify_invalid(handler, client_id, &reason).await;
+    VerificationResponse::Invalid { reason }
+}
+
+/// Tells the handler about a rejected proof. A failing handler is only logged, so the client is
+/// told why its proof was rejected whatever the handler does.
+async fn notify_invalid(handler: &dyn ProofHandler, client_id: &str, reason: &str) {
+    if let Err(e) = handler.on_invalid(client_id, reason).await {
+        error!("Invalid proof handler failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        setup::generate_keys,
+        test_utils::{keys, prove_with, request, verifier_config, write_key, Keys, ProductCircuit},
+    };
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Asks for a proof of the current product, which moves on by one after every accepted proof.
+    struct CountingChallenge(AtomicU64);
+
+    impl ChallengeProvider for CountingChallenge {
+        type Challenge = u64;
+
+        fn issue(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+
+        fn rotate(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn validate(&self, _public_inputs: &[Fr]) -> bool {
+            true
+        }
+
+        fn expected_inputs(&self) -> Option<Vec<Fr>> {
+            Some(vec![Fr::from(self.issue())])
+        }
+    }
+
+    #[tokio::test]
+    async fn challenge_policy_follows_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let verifier = Verifier::new(&verifier_config(dir.path()))
+            .unwrap()
+            .with_challenge_provider(CountingChallenge(AtomicU64::new(15)))
+            .with_input_policy(InputPolicy::new().require_challenge(0..1));
+
+        let response = verifier.verify(&request("client", 3, 5)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+
+        match verifier.verify(&request("client", 3, 7)).await {
+            VerificationResponse::Rejected {
+                violation: Some(violation),
+                ..
+            } => assert_eq!(violation.rule, "challenge"),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let response = verifier.verify(&request("client", 2, 8)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+    }
+
+    #[tokio::test]
+    async fn resubmission_with_fresh_nonce_is_duplicate() {
+        let dir = tempfile::tempdir().unwrap();
+        let verifier = Verifier::new(&verifier_config(dir.path())).unwrap();
+
+        let mut first = request("client", 3, 5);
+        first.nonce = Some(verifier.issue_nonce().unwrap());
+        let response = verifier.verify(&first).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+
+        let mut replayed = first.clone();
+        replayed.client_id = "other".to_string();
+        replayed.nonce = Some(verifier.issue_nonce().unwrap());
+        let response = verifier.verify(&replayed).await;
+        assert!(matches!(response, VerificationResponse::Duplicate {}));
+    }
+
+    #[tokio::test]
+    async fn shared_nonce_is_accepted_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let verifier = Verifier::new(&verifier_config(dir.path())).unwrap();
+
+        let nonce = verifier.issue_nonce().unwrap();
+        let mut first = request("client", 3, 5);
+        first.nonce = Some(nonce.clone());
+        let mut second = request("client", 2, 7);
+        second.nonce = Some(nonce);
+
+        let (a, b) = tokio::join!(verifier.verify(&first), verifier.verify(&second));
+        let valid = [&a, &b]
+            .iter()
+            .filter(|response| matches!(response, VerificationResponse::Valid { .. }))
+            .count();
+        assert_eq!(valid, 1, "responses: {:?}, {:?}", a, b);
+        for response in [a, b] {
+            match response {
+                VerificationResponse::Valid { .. } => {}
+                VerificationResponse::StaleNonce {} => {}
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_proof_keeps_its_nonce() {
+        let dir = tempfile::tempdir().unwrap();
+        let verifier = Verifier::new(&verifier_config(dir.path()))
+            .unwrap()
+            .with_input_policy(InputPolicy::new().require_one_of(0, vec![Fr::from(15u64)]));
+
+        let nonce = verifier.issue_nonce().unwrap();
+        let mut rejected = request("client", 3, 7);
+        rejected.nonce = Some(nonce.clone());
+        let response = verifier.verify(&rejected).await;
+        assert!(matches!(response, VerificationResponse::Rejected { .. }));
+
+        let mut accepted = request("client", 3, 5);
+        accepted.nonce = Some(nonce);
+        let response = verifier.verify(&accepted).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+    }
+
+    #[tokio::test]
+    async fn batch_fallback_names_the_corrupted_proof() {
+        let dir = tempfile::tempdir().unwrap();
+        let verifier = Verifier::new(&verifier_config(dir.path())).unwrap();
+
+        let (valid, _) = prove_with(&keys().0, 3, 5);
+        let (mut corrupted, inputs) = prove_with(&keys().0, 2, 7);
+        corrupted.c = valid.c;
+        let corrupted =
+            ProofRequest::from_proof("client", DEFAULT_CIRCUIT_ID, &corrupted, &inputs).unwrap();
+        let requests = vec![
+            request("client", 3, 5),
+            request("client", 11, 13),
+            corrupted,
+            request("client", 2, 9),
+        ];
+
+        let responses = verifier.verify_batch(&requests).await;
+        assert_eq!(responses.len(), requests.len());
+        for (index, response) in responses.iter().enumerate() {
+            if index == 2 {
+                assert!(
+                    matches!(response, VerificationResponse::Invalid { .. }),
+                    "{:?}",
+                    response
+                );
+            } else {
+                assert!(
+                    matches!(response, VerificationResponse::Valid { .. }),
+                    "{:?}",
+                    response
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_invalid_handler_keeps_the_rejection() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        let verifier = Verifier::new(&verifier_config(dir.path()))
+            .unwrap()
+            .with_input_policy(InputPolicy::new().require_one_of(0, vec![Fr::from(15u64)]))
+            .with_invalid_proof_handler(move |_, _| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("handler is down"))
+            });
+
+        let (valid, _) = prove_with(&keys().0, 3, 5);
+        let (mut corrupted, inputs) = prove_with(&keys().0, 5, 3);
+        corrupted.c = valid.c;
+        let corrupted =
+            ProofRequest::from_proof("client", DEFAULT_CIRCUIT_ID, &corrupted, &inputs).unwrap();
+        let mut malformed = request("client", 3, 5);
+        malformed.public_inputs = vec!["fifteen".to_string()];
+
+        let response = verifier.verify(&corrupted).await;
+        assert!(
+            matches!(response, VerificationResponse::Invalid { .. }),
+            "{:?}",
+            response
+        );
+        let response = verifier.verify(&malformed).await;
+        assert!(
+            matches!(response, VerificationResponse::Malformed { .. }),
+            "{:?}",
+            response
+        );
+        let response = verifier.verify(&request("client", 3, 7)).await;
+        assert!(
+            matches!(response, VerificationResponse::Rejected { .. }),
+            "{:?}",
+            response
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    fn new_keys() -> Keys {
+        generate_keys(Box::new(ProductCircuit { a: 1, b: 1 })).unwrap()
+    }
+
+    fn request_with(keys: &Keys, a: u64, b: u64) -> ProofRequest {
+        let (proof, inputs) = prove_with(&keys.0, a, b);
+        ProofRequest::from_proof("client", DEFAULT_CIRCUIT_ID, &proof, &inputs).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reload_accepts_previous_key_during_grace_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = verifier_config(dir.path());
+        config.key_grace_secs = 1;
+        let verifier = Verifier::new(&config).unwrap();
+        let old_fingerprint = verifier.key_fingerprint(DEFAULT_CIRCUIT_ID).unwrap();
+
+        assert!(verifier.reload_keys().await.unwrap().is_empty());
+
+        let replacement = new_keys();
+        write_key(
+            &config.verification_keys[DEFAULT_CIRCUIT_ID],
+            &replacement.1,
+        );
+        assert_eq!(
+            verifier.reload_keys().await.unwrap(),
+            vec![DEFAULT_CIRCUIT_ID.to_string()]
+        );
+        assert_ne!(
+            verifier.key_fingerprint(DEFAULT_CIRCUIT_ID).unwrap(),
+            old_fingerprint
+        );
+
+        let response = verifier.verify(&request_with(&replacement, 3, 5)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+        let response = verifier.verify(&request("client", 2, 7)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let response = verifier.verify(&request("client", 2, 9)).await;
+        assert!(
+            matches!(response, VerificationResponse::Invalid { .. }),
+            "{:?}",
+            response
+        );
+        let response = verifier.verify(&request_with(&replacement, 2, 11)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+    }
+
+    #[tokio::test]
+    async fn concurrent_reloads_keep_the_grace_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = verifier_config(dir.path());
+        let verifier = Verifier::new(&config).unwrap();
+
+        write_key(&config.verification_keys[DEFAULT_CIRCUIT_ID], &new_keys().1);
+        let (first, second) = tokio::join!(verifier.reload_keys(), verifier.reload_keys());
+        assert_eq!(first.unwrap().len() + second.unwrap().len(), 1);
+
+        let response = verifier.verify(&request("client", 3, 5)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+    }
+
+    #[tokio::test]
+    async fn failed_reload_keeps_the_current_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = verifier_config(dir.path());
+        let verifier = Verifier::new(&config).unwrap();
+
+        std::fs::write(&config.verification_keys[DEFAULT_CIRCUIT_ID], b"not a key").unwrap();
+        assert!(verifier.reload_keys().await.is_err());
+        let response = verifier.verify(&request("client", 3, 5)).await;
+        assert!(matches!(response, VerificationResponse::Valid { .. }));
+    }
+
+    fn decode_reason(request: &ProofRequest) -> Option<MalformedReason> {
+        decode_request(request, 1).err().map(|(reason, _)| reason)
+    }
+
+    #[test]
+    fn decode_request_reports_what_is_malformed() {
+        let valid = request("client", 3, 5);
+        assert_eq!(decode_reason(&valid), None);
+
+        let mut extra_input = valid.clone();
+        extra_input.public_inputs.push("1".to_string());
+        assert_eq!(
+            decode_reason(&extra_input),
+            Some(MalformedReason::InputCount)
+        );
+
+        let mut not_base64 = valid.clone();
+        not_base64.proof.0 = "not base64!".to_string();
+        assert_eq!(
+            decode_reason(&not_base64),
+            Some(MalformedReason::InvalidBase64)
+        );
+
+        let mut short = valid.clone();
+        short.proof.0 = STANDARD.encode([0u8; 32]);
+        assert_eq!(decode_reason(&short), Some(MalformedReason::ProofLength));
+
+        let mut off_curve = valid.clone();
+        let length = Proof::<Bls12_381>::default().uncompressed_size();
+        off_curve.proof.0 = STANDARD.encode(vec![0xff; length]);
+        assert_eq!(
+            decode_reason(&off_curve),
+            Some(MalformedReason::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn decode_request_rejects_non_canonical_inputs() {
+        const MODULUS: &str =
+            "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+        for input in [
+            "", "015", "+15", " 15", "15 ", "-15", "0x0f", "1e3", MODULUS,
+        ] {
+            let mut request = request("client", 3, 5);
+            request.public_inputs = vec![input.to_string()];
+            assert_eq!(
+                decode_reason(&request),
+                Some(MalformedReason::NonCanonicalInput),
+                "{:?}",
+                input
+            );
+        }
+
+        let mut request = request("client", 3, 5);
+        request.public_inputs = vec!["0".to_string()];
+        assert_eq!(decode_reason(&request), None);
+    }
+}
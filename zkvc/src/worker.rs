@@ -0,0 +1,387 @@
+use crate::{client::ClientApp, response::VerificationResponse};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{future::Future, marker::PhantomData, pin::Pin, time::Duration};
+use tokio::sync::watch;
+
+/// Boxed future returned by `TaskSource::next_task`.
+pub type TaskFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, anyhow::Error>> + Send + 'a>>;
+
+/// What a `TaskSource` has for the worker next.
+pub enum NextTask<T> {
+    Task(T),
+    /// No work right now, the worker asks again after its idle backoff.
+    Idle,
+    /// No more work will come, the worker stops.
+    Exhausted,
+}
+
+/// Where `ClientApp::run_worker` takes its tasks from.
+///
+/// Errors are logged and treated like `NextTask::Idle`, so a server that is briefly unreachable
+/// does not stop the worker.
+pub trait TaskSource: Send {
+    type Task: Send;
+
+    fn next_task<'a>(&'a mut self, client: &'a ClientApp) -> TaskFuture<'a, NextTask<Self::Task>>;
+}
+
+/// Uses the server's current challenge for the client's circuit as the next task.
+pub struct ChallengeSource<T> {
+    _challenge: PhantomData<fn() -> T>,
+}
+
+impl<T> ChallengeSource<T> {
+    pub fn new() -> Self {
+        Self {
+            _challenge: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for ChallengeSource<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> TaskSource for ChallengeSource<T> {
+    type Task = T;
+
+    fn next_task<'a>(&'a mut self, client: &'a ClientApp) -> TaskFuture<'a, NextTask<T>> {
+        Box::pin(async move { Ok(NextTask::Task(client.fetch_challenge().await?)) })
+    }
+}
+
+/// Hands out the items of an iterator, then stops the worker.
+pub struct IterSource<I> {
+    tasks: I,
+}
+
+impl<I: Iterator> IterSource<I> {
+    pub fn new(tasks: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            tasks: tasks.into_iter(),
+        }
+    }
+}
+
+impl<I> TaskSource for IterSource<I>
+where
+    I: Iterator + Send,
+    I::Item: Send,
+{
+    type Task = I::Item;
+
+    fn next_task<'a>(&'a mut self, _client: &'a ClientApp) -> TaskFuture<'a, NextTask<I::Item>> {
+        let next = match self.tasks.next() {
+            Some(task) => NextTask::Task(task),
+            None => NextTask::Exhausted,
+        };
+        Box::pin(async move { Ok(next) })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerConfig {
+    /// Wait after the task source had no work or failed, doubled for every further idle round.
+    #[serde(default = "default_idle_backoff_secs")]
+    pub idle_backoff_secs: u64,
+    #[serde(default = "default_max_idle_backoff_secs")]
+    pub max_idle_backoff_secs: u64,
+    /// Stop after this many tasks. Without a limit the worker runs until it is stopped or the
+    /// task source is exhausted.
+    #[serde(default)]
+    pub max_tasks: Option<u64>,
+}
+
+fn default_idle_backoff_secs() -> u64 {
+    1
+}
+
+fn default_max_idle_backoff_secs() -> u64 {
+    60
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            idle_backoff_secs: default_idle_backoff_secs(),
+            max_idle_backoff_secs: default_max_idle_backoff_secs(),
+            max_tasks: None,
+        }
+    }
+}
+
+/// Counts of what happened to the tasks a worker took, returned by `ClientApp::run_worker`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct WorkerStats {
+    pub tasks: u64,
+    pub valid: u64,
+    pub invalid: u64,
+    /// Refused by the server's policies, challenge or replay checks.
+    pub rejected: u64,
+    /// Turned away because the server was busy, rate limited or had banned the client.
+    pub deferred: u64,
+    /// Could not be built, proved or submitted.
+    pub failed: u64,
+}
+
+impl WorkerStats {
+    pub(crate) fn record(&mut self, response: &VerificationResponse) {
+        match response {
+            VerificationResponse::Valid { .. } => self.valid += 1,
+            VerificationResponse::Invalid { .. } | VerificationResponse::Malformed { .. } => {
+                self.invalid += 1
+            }
+            VerificationResponse::Rejected { .. }
+            | VerificationResponse::Duplicate {}
+            | VerificationResponse::StaleNonce {}
+            | VerificationResponse::Unauthorized { .. } => self.rejected += 1,
+            VerificationResponse::Retry { .. }
+            | VerificationResponse::RateLimited { .. }
+            | VerificationResponse::Banned { .. } => self.deferred += 1,
+            VerificationResponse::Error { .. } => self.failed += 1,
+        }
+    }
+}
+
+/// Doubling wait between idle rounds, reset once a task comes in.
+pub(crate) struct Backoff {
+    base: Duration,
+    max: Duration,
+    rounds: u32,
+}
+
+impl Backoff {
+    pub(crate) fn new(config: &WorkerConfig) -> Self {
+        Self {
+            base: Duration::from_secs(config.idle_backoff_secs),
+            max: Duration::from_secs(config.max_idle_backoff_secs.max(config.idle_backoff_secs)),
+            rounds: 0,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Duration {
+        let wait = self
+            .base
+            .saturating_mul(1 << self.rounds.min(16))
+            .min(self.max);
+        self.rounds = self.rounds.saturating_add(1);
+        wait
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.rounds = 0;
+    }
+}
+
+pub(crate) fn is_stopped(stop: &Option<watch::Receiver<bool>>) -> bool {
+    stop.as_ref().is_some_and(|stop| *stop.borrow())
+}
+
+/// Sleeps for `duration`, returning `false` if the stop signal fired first.
+pub(crate) async fn pause(duration: Duration, stop: &mut Option<watch::Receiver<bool>>) -> bool {
+    let Some(stop) = stop else {
+        tokio::time::sleep(duration).await;
+        return true;
+    };
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        Ok(_) = stop.wait_for(|stopped| *stopped) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::ConstraintGenerator,
+        server::{ServerApp, ServerHandle},
+        test_utils::{client_config, server_config, ProductCircuit},
+    };
+    use ark_bls12_381::Fr;
+    use std::{collections::VecDeque, path::Path};
+
+    /// Hands out a scripted sequence of answers, then reports exhaustion.
+    struct StubSource {
+        answers: VecDeque<Result<NextTask<(u64, u64)>, anyhow::Error>>,
+        calls: usize,
+    }
+
+    impl StubSource {
+        fn new(
+            answers: impl IntoIterator<Item = Result<NextTask<(u64, u64)>, anyhow::Error>>,
+        ) -> Self {
+            Self {
+                answers: answers.into_iter().collect(),
+                calls: 0,
+            }
+        }
+    }
+
+    impl TaskSource for &mut StubSource {
+        type Task = (u64, u64);
+
+        fn next_task<'a>(
+            &'a mut self,
+            _client: &'a ClientApp,
+        ) -> TaskFuture<'a, NextTask<(u64, u64)>> {
+            self.calls += 1;
+            let next = self.answers.pop_front().unwrap_or(Ok(NextTask::Exhausted));
+            Box::pin(async move { next })
+        }
+    }
+
+    fn product((a, b): (u64, u64)) -> Result<Box<dyn ConstraintGenerator<Fr>>, anyhow::Error> {
+        Ok(Box::new(ProductCircuit { a, b }))
+    }
+
+    fn start_server(dir: &Path) -> ServerHandle {
+        ServerApp::new(server_config(dir)).unwrap().start().unwrap()
+    }
+
+    fn worker(dir: &Path, handle: &ServerHandle, worker: WorkerConfig) -> ClientApp {
+        let mut config = client_config(dir, handle.local_addr(), "worker");
+        config.worker = worker;
+        ClientApp::new(config).unwrap()
+    }
+
+    fn no_backoff() -> WorkerConfig {
+        WorkerConfig {
+            idle_backoff_secs: 0,
+            max_idle_backoff_secs: 0,
+            max_tasks: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn worker_proves_tasks_until_the_source_is_exhausted() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+        let client = worker(dir.path(), &handle, no_backoff());
+
+        let mut source = StubSource::new([
+            Ok(NextTask::Task((3, 5))),
+            Ok(NextTask::Idle),
+            Err(anyhow::anyhow!("server unreachable")),
+            Ok(NextTask::Task((2, 7))),
+            Ok(NextTask::Task((5, 3))),
+        ]);
+        let stats = client.run_worker(&mut source, product).await.unwrap();
+        assert_eq!(
+            stats,
+            WorkerStats {
+                tasks: 3,
+                valid: 2,
+                rejected: 1,
+                ..WorkerStats::default()
+            }
+        );
+        assert_eq!(source.calls, 6);
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn worker_stops_at_max_tasks_and_on_failed_circuits() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+        let client = worker(
+            dir.path(),
+            &handle,
+            WorkerConfig {
+                max_tasks: Some(2),
+                ..no_backoff()
+            },
+        );
+
+        let mut source = StubSource::new([
+            Ok(NextTask::Task((0, 0))),
+            Ok(NextTask::Task((3, 5))),
+            Ok(NextTask::Task((2, 7))),
+        ]);
+        let stats = client
+            .run_worker(&mut source, |task| match task {
+                (0, 0) => Err(anyhow::anyhow!("no circuit for this task")),
+                task => product(task),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            stats,
+            WorkerStats {
+                tasks: 2,
+                valid: 1,
+                failed: 1,
+                ..WorkerStats::default()
+            }
+        );
+        assert_eq!(source.calls, 2);
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_worker_returns_on_stop_signal() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = start_server(dir.path());
+        let (stop, stop_signal) = watch::channel(false);
+        let client = worker(
+            dir.path(),
+            &handle,
+            WorkerConfig {
+                idle_backoff_secs: 60,
+                max_idle_backoff_secs: 60,
+                max_tasks: None,
+            },
+        )
+        .with_stop_signal(stop_signal);
+
+        let mut source = StubSource::new((0..10).map(|_| Ok(NextTask::Idle)));
+        let run = client.run_worker(&mut source, product);
+        let (stats, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(run, async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                stop.send(true).unwrap();
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!(stats.unwrap(), WorkerStats::default());
+        assert_eq!(source.calls, 1);
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn worker_gives_up_when_the_server_refuses_the_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = server_config(dir.path());
+        config.require_signatures = true;
+        let handle = ServerApp::new(config).unwrap().start().unwrap();
+        let client = worker(dir.path(), &handle, no_backoff());
+
+        let mut source = StubSource::new([Ok(NextTask::Task((3, 5))), Ok(NextTask::Task((2, 7)))]);
+        assert!(client.run_worker(&mut source, product).await.is_err());
+        assert_eq!(source.calls, 1);
+
+        handle.stop(true).await;
+        handle.join().await.unwrap();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit_and_resets() {
+        let mut backoff = Backoff::new(&WorkerConfig {
+            idle_backoff_secs: 1,
+            max_idle_backoff_secs: 5,
+            max_tasks: None,
+        });
+        let waits: Vec<_> = (0..4).map(|_| backoff.next().as_secs()).collect();
+        assert_eq!(waits, [1, 2, 4, 5]);
+        backoff.reset();
+        assert_eq!(backoff.next(), Duration::from_secs(1));
+    }
+}